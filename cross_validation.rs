@@ -0,0 +1,333 @@
+pub mod cross_validation {
+    #![allow(dead_code)]
+    use crate::{Backtest, DataFeed, PramVal, Prams};
+    use crate::indicators::stats::{combinations, quantile, sharpe_ratio};
+    use crate::optimizer::optimizer::Optimizer;
+    use std::collections::HashMap;
+
+    // Bars are cut into `groups` contiguous blocks and every choice of `test_groups` blocks
+    // is tested once. With test_groups = 1 this is purged k-fold; with more it is
+    // combinatorial purged cross-validation, whose test blocks combine into many paths.
+    pub struct CrossValidation {
+        pub groups: usize,
+        pub test_groups: usize,
+        // training bars dropped before each test block, whose labels would overlap it
+        pub purge: usize,
+        // training bars dropped after each test block, whose features would overlap it
+        pub embargo: usize,
+        // bars before each test block handed to the runner so indicators are ready
+        pub warmup: usize,
+        pub periods_per_year: f64,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct CvSplit {
+        pub test_groups: Vec<usize>,
+        // half-open bar ranges left for training after purging and embargo
+        pub train: Vec<(usize, usize)>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct SplitResult {
+        pub test_groups: Vec<usize>,
+        pub params: Prams,
+        pub sharpe: f64,
+        // per-bar returns of each test block, in the order of `test_groups`
+        pub returns: Vec<Vec<f64>>,
+    }
+
+    pub struct CvReport {
+        pub splits: Vec<SplitResult>,
+        // equity of each backtest path, starting at 1.0
+        pub paths: Vec<Vec<f64>>,
+        pub path_sharpe: Vec<f64>,
+    }
+
+    impl CrossValidation {
+        pub fn k_fold(folds: usize) -> Self {
+            Self::combinatorial(folds, 1)
+        }
+
+        pub fn combinatorial(groups: usize, test_groups: usize) -> Self {
+            CrossValidation {
+                groups,
+                test_groups,
+                purge: 0,
+                embargo: 0,
+                warmup: 0,
+                periods_per_year: 365.0,
+            }
+        }
+
+        pub fn set_purge(&mut self, bars: usize) {
+            self.purge = bars;
+        }
+
+        pub fn set_embargo(&mut self, bars: usize) {
+            self.embargo = bars;
+        }
+
+        pub fn set_warmup(&mut self, bars: usize) {
+            self.warmup = bars;
+        }
+
+        // Half-open bar range of each block; the first len % groups blocks get one extra bar
+        pub fn group_bounds(&self, len: usize) -> Vec<(usize, usize)> {
+            let mut bounds = Vec::with_capacity(self.groups);
+            let mut start = 0;
+            for g in 0..self.groups {
+                let size = len / self.groups + if g < len % self.groups { 1 } else { 0 };
+                bounds.push((start, start + size));
+                start += size;
+            }
+            bounds
+        }
+
+        pub fn splits(&self, len: usize) -> Result<Vec<CvSplit>, Box<dyn std::error::Error>> {
+            if self.groups < 2 || self.test_groups == 0 || self.test_groups >= self.groups {
+                return Err("Need at least two groups and fewer test groups than groups".into());
+            }
+            if len < self.groups {
+                return Err("Not enough data for the number of groups".into());
+            }
+            let bounds = self.group_bounds(len);
+
+            let mut splits = Vec::new();
+            for test_groups in combinations(self.groups, self.test_groups) {
+                let mut train = vec![true; len];
+                for &g in &test_groups {
+                    let (start, end) = bounds[g];
+                    for flag in &mut train[start.saturating_sub(self.purge)..(end + self.embargo).min(len)] {
+                        *flag = false;
+                    }
+                }
+                let mut ranges = Vec::new();
+                let mut i = 0;
+                while i < len {
+                    if !train[i] {
+                        i += 1;
+                        continue;
+                    }
+                    let start = i;
+                    while i < len && train[i] {
+                        i += 1;
+                    }
+                    ranges.push((start, i));
+                }
+                splits.push(CvSplit { test_groups, train: ranges });
+            }
+            Ok(splits)
+        }
+
+        // `fit` chooses parameters from the training slices of a split; `make_runner` builds
+        // the runner that trades them on each test block plus `warmup` bars before it.
+        // Test returns are measured from the bar before the block opens, except for the
+        // first block, which warms up inside the runner from the start of the data. A block
+        // whose run fails fails the whole run, so every path covers every bar.
+        pub fn run<F, M, R>(&self, data: &DataFeed, fit: F, make_runner: M) -> Result<CvReport, Box<dyn std::error::Error>>
+        where
+            F: Fn(&[DataFeed]) -> Result<Prams, Box<dyn std::error::Error>>,
+            M: Fn(DataFeed) -> R,
+            R: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>>,
+        {
+            let len = data.Date.len();
+            let bounds = self.group_bounds(len);
+            let mut results = Vec::new();
+
+            for split in self.splits(len)? {
+                let train = split
+                    .train
+                    .iter()
+                    .map(|(start, end)| data.slice(*start, *end))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let params = fit(&train)?;
+
+                let mut returns = Vec::new();
+                for &g in &split.test_groups {
+                    let (start, end) = bounds[g];
+                    let runner = make_runner(data.slice(start.saturating_sub(self.warmup), end)?);
+                    let mut prams = params.clone();
+                    prams.data.insert("verbose".to_string(), PramVal::Bool(false));
+                    let mut backtest = runner(&prams)
+                        .map_err(|e| format!("Test group {} of split {:?} failed: {}", g, split.test_groups, e))?;
+                    let keep = (end - start + 1).min(backtest.equity.len());
+                    backtest.equity = backtest.equity.split_off(backtest.equity.len() - keep);
+                    returns.push(backtest.returns());
+                }
+                let all: Vec<f64> = returns.iter().flatten().cloned().collect();
+                results.push(SplitResult {
+                    test_groups: split.test_groups,
+                    params,
+                    sharpe: sharpe_ratio(&all, self.periods_per_year),
+                    returns,
+                });
+            }
+
+            let paths = self.paths(&results);
+            let path_sharpe = paths
+                .iter()
+                .map(|equity| {
+                    let returns: Vec<f64> = equity.windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+                    sharpe_ratio(&returns, self.periods_per_year)
+                })
+                .collect();
+            Ok(CvReport {
+                splits: results,
+                paths,
+                path_sharpe,
+            })
+        }
+
+        // Each block is tested by the same number of splits; path j takes every block's
+        // returns from the j-th split that tested it
+        fn paths(&self, results: &[SplitResult]) -> Vec<Vec<f64>> {
+            let mut by_group: Vec<Vec<&Vec<f64>>> = vec![Vec::new(); self.groups];
+            for result in results {
+                for (k, &g) in result.test_groups.iter().enumerate() {
+                    by_group[g].push(&result.returns[k]);
+                }
+            }
+            let count = by_group.iter().map(|v| v.len()).min().unwrap_or(0);
+            (0..count)
+                .map(|j| {
+                    let mut equity = vec![1.0];
+                    for group in &by_group {
+                        for r in group[j] {
+                            let last = *equity.last().unwrap();
+                            equity.push(last * (1.0 + r));
+                        }
+                    }
+                    equity
+                })
+                .collect()
+        }
+    }
+
+    // Parameters with the best mean objective over every training slice. Slices too short
+    // for a parameter set count against it, and slices too short for all are ignored.
+    pub fn grid_fit<'a, M, R>(optimizer: &'a Optimizer, base: &'a Prams, make_runner: M) -> impl Fn(&[DataFeed]) -> Result<Prams, Box<dyn std::error::Error>> + 'a
+    where
+        M: Fn(DataFeed) -> R + 'a,
+        R: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync,
+    {
+        move |train: &[DataFeed]| {
+            let mut scores: HashMap<String, (Prams, f64, usize)> = HashMap::new();
+            let mut slices = 0;
+            for feed in train {
+                let results = match optimizer.evaluate(optimizer.grid(base), &make_runner(feed.get_ohlcv())) {
+                    Ok(results) => results,
+                    Err(_) => continue,
+                };
+                slices += 1;
+                for result in results {
+                    let entry = scores.entry(key(&result.params)).or_insert((result.params.clone(), 0.0, 0));
+                    entry.1 += result.objective;
+                    entry.2 += 1;
+                }
+            }
+            scores
+                .into_values()
+                .filter(|(_, _, n)| *n == slices)
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(params, _, _)| params)
+                .ok_or_else(|| "No parameter set ran on every training slice".into())
+        }
+    }
+
+    fn key(params: &Prams) -> String {
+        let mut pairs: Vec<String> = params.data.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        pairs.sort();
+        pairs.join(" ")
+    }
+
+    fn print_distribution(label: &str, values: &[f64]) {
+        if values.is_empty() {
+            return;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt();
+        let positive = values.iter().filter(|v| **v > 0.0).count() as f64 / values.len() as f64;
+        println!(
+            "{} ({}): mean {:.4} std {:.4} min {:.4} p5 {:.4} median {:.4} p95 {:.4} max {:.4} positive {:.2}",
+            label, values.len(), mean, std, sorted[0], quantile(&sorted, 0.05), quantile(&sorted, 0.5),
+            quantile(&sorted, 0.95), sorted[sorted.len() - 1], positive
+        );
+    }
+
+    impl CvReport {
+        pub fn print(&self) {
+            println!("...Cross-Validation...");
+            for split in &self.splits {
+                println!("test {:?} | OOS sharpe {:.4} | {}", split.test_groups, split.sharpe, key(&split.params));
+            }
+            let split_sharpe: Vec<f64> = self.splits.iter().map(|s| s.sharpe).collect();
+            print_distribution("Split OOS Sharpe", &split_sharpe);
+            print_distribution("Path OOS Sharpe", &self.path_sharpe);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn feed(len: usize) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for k in 0..len {
+                let c = 100.0 + k as f64;
+                ohlcv.Date.push(format!("bar{}", k));
+                ohlcv.Open.push(c);
+                ohlcv.High.push(c);
+                ohlcv.Low.push(c);
+                ohlcv.Close.push(c);
+                ohlcv.Adj_Close.push(c);
+                ohlcv.Volume.push(1000);
+            }
+            ohlcv
+        }
+
+        fn fit(_: &[DataFeed]) -> Result<Prams, Box<dyn std::error::Error>> {
+            Ok(Prams { data: HashMap::new() })
+        }
+
+        // equity tracks the close, failing on the block whose slice opens at `fail_at`
+        fn runner(slice: DataFeed, fail_at: f64) -> impl Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> {
+            move |_| {
+                if slice.Close[0] == fail_at {
+                    return Err("runner broke".into());
+                }
+                let mut backtest = Backtest::new(slice.clone());
+                backtest.equity = slice.Close.clone();
+                Ok(backtest)
+            }
+        }
+
+        #[test]
+        fn every_path_covers_every_bar() {
+            let mut cv = CrossValidation::combinatorial(4, 2);
+            cv.set_warmup(3);
+            let data = feed(40);
+            let report = cv.run(&data, fit, |slice| runner(slice, -1.0)).unwrap();
+
+            assert_eq!(report.splits.len(), 6);
+            assert_eq!(report.paths.len(), 3);
+            for path in &report.paths {
+                assert_eq!(path.len(), 40);
+                assert!((path[39] - data.Close[39] / data.Close[0]).abs() < 1e-9);
+            }
+        }
+
+        #[test]
+        fn a_failed_test_block_fails_the_run() {
+            let mut cv = CrossValidation::k_fold(4);
+            cv.set_warmup(3);
+            let data = feed(40);
+            // the third block opens at bar 20, three warm-up bars earlier
+            let result = cv.run(&data, fit, |slice| runner(slice, data.Close[17]));
+            let error = result.err().unwrap().to_string();
+            assert!(error.contains("Test group 2"));
+            assert!(error.contains("runner broke"));
+        }
+    }
+}
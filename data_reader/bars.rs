@@ -0,0 +1,317 @@
+pub mod bars {
+    #![allow(dead_code)]
+    use crate::data_reader::data::data::DataFeed;
+    use crate::indicators::indicators::Indicators;
+
+    pub enum BrickSize {
+        Fixed(f64),
+        // box size follows ATR(period) on the bar the brick is built, so no future data is used
+        Atr(i64),
+    }
+
+    // Bars built from a source feed. `bars` is an ordinary DataFeed for indicators and
+    // `Backtest`; source_start/source_end map every bar back to the source bars it spans.
+    pub struct BarSeries {
+        pub bars: DataFeed,
+        pub source_start: Vec<usize>,
+        pub source_end: Vec<usize>,
+    }
+
+    impl BarSeries {
+        fn new() -> Self {
+            BarSeries {
+                bars: DataFeed::new(),
+                source_start: Vec::new(),
+                source_end: Vec::new(),
+            }
+        }
+
+        fn push(&mut self, source: &DataFeed, start: usize, end: usize, ohlc: (f64, f64, f64, f64), volume: u64) {
+            let (open, high, low, close) = ohlc;
+            self.bars.Date.push(source.Date[end].clone());
+            self.bars.Open.push(open);
+            self.bars.High.push(high);
+            self.bars.Low.push(low);
+            self.bars.Close.push(close);
+            self.bars.Adj_Close.push(close);
+            self.bars.Volume.push(volume);
+            self.source_start.push(start);
+            self.source_end.push(end);
+        }
+
+        // A feed aligned with `bars` that holds real source prices, for filling orders.
+        // Close is the source close of the completing bar. Open is the source open of the
+        // first spanned bar, or the previous bar's completing close when both bars finished
+        // inside the same source bar.
+        pub fn execution_feed(&self, source: &DataFeed) -> DataFeed {
+            let mut exec = DataFeed::new();
+            for k in 0..self.bars.Date.len() {
+                let (start, end) = (self.source_start[k], self.source_end[k]);
+                let open = if k > 0 && start <= self.source_end[k - 1] {
+                    source.Close[self.source_end[k - 1]]
+                } else {
+                    source.Open[start]
+                };
+                let high = source.High[start..=end].iter().cloned().fold(f64::MIN, f64::max);
+                let low = source.Low[start..=end].iter().cloned().fold(f64::MAX, f64::min);
+                exec.Date.push(source.Date[end].clone());
+                exec.Open.push(open);
+                exec.High.push(high.max(open));
+                exec.Low.push(low.min(open));
+                exec.Close.push(source.Close[end]);
+                exec.Adj_Close.push(source.Adj_Close[end]);
+                exec.Volume.push(self.bars.Volume[k]);
+            }
+            exec
+        }
+    }
+
+    pub struct Bars {
+        pub ohlcv: DataFeed,
+    }
+
+    impl Bars {
+        pub fn heikin_ashi(&mut self) -> Result<BarSeries, Box<dyn std::error::Error>> {
+            let src = &self.ohlcv;
+            if src.Date.is_empty() {
+                return Err("No data available".into());
+            }
+            let mut series = BarSeries::new();
+            let mut prev_open = 0.0;
+            let mut prev_close = 0.0;
+
+            for i in 0..src.Date.len() {
+                let close = (src.Open[i] + src.High[i] + src.Low[i] + src.Close[i]) / 4.0;
+                let open = if i == 0 {
+                    (src.Open[i] + src.Close[i]) / 2.0
+                } else {
+                    (prev_open + prev_close) / 2.0
+                };
+                let high = src.High[i].max(open).max(close);
+                let low = src.Low[i].min(open).min(close);
+                series.push(src, i, i, (open, high, low, close), src.Volume[i]);
+                prev_open = open;
+                prev_close = close;
+            }
+            Ok(series)
+        }
+
+        // Bricks are built from closes. A new brick needs a full box beyond the last
+        // brick, so a reversal needs two boxes from the last brick's close.
+        pub fn renko(&mut self, size: BrickSize) -> Result<BarSeries, Box<dyn std::error::Error>> {
+            let src = self.ohlcv.get_ohlcv();
+            let len = src.Close.len();
+            let (boxes, first) = match size {
+                BrickSize::Fixed(size) => {
+                    if size <= 0.0 {
+                        return Err("Brick size must be positive".into());
+                    }
+                    (vec![size; len], 0)
+                }
+                BrickSize::Atr(period) => {
+                    let mut indicators = Indicators { ohlcv: src.get_ohlcv() };
+                    (indicators.ATR(period)?, period as usize)
+                }
+            };
+            if first >= len {
+                return Err("Not enough data to build bricks".into());
+            }
+
+            let mut series = BarSeries::new();
+            let mut top = src.Close[first];
+            let mut bottom = src.Close[first];
+            let mut start = first + 1;
+
+            for (i, &size) in boxes.iter().enumerate().skip(first + 1) {
+                if size <= 0.0 {
+                    continue;
+                }
+                let price = src.Close[i];
+                // the first brick completed on a bar carries the volume since the last brick;
+                // further bricks on the same bar share it and carry none
+                let mut volume = src.Volume[start..=i].iter().sum::<u64>();
+                let mut formed = false;
+                while price >= top + size {
+                    series.push(&src, start, i, (top, top + size, top, top + size), volume);
+                    bottom = top;
+                    top += size;
+                    volume = 0;
+                    start = i;
+                    formed = true;
+                }
+                while price <= bottom - size {
+                    series.push(&src, start, i, (bottom, bottom, bottom - size, bottom - size), volume);
+                    top = bottom;
+                    bottom -= size;
+                    volume = 0;
+                    start = i;
+                    formed = true;
+                }
+                if formed {
+                    start = i + 1;
+                }
+            }
+            Ok(series)
+        }
+
+        // Each bar spans whole source bars until its high-low range reaches `range`
+        pub fn range_bars(&mut self, range: f64) -> Result<BarSeries, Box<dyn std::error::Error>> {
+            if range <= 0.0 {
+                return Err("Range must be positive".into());
+            }
+            self.aggregate(|src, start, end| {
+                let high = src.High[start..=end].iter().cloned().fold(f64::MIN, f64::max);
+                let low = src.Low[start..=end].iter().cloned().fold(f64::MAX, f64::min);
+                high - low >= range
+            })
+        }
+
+        // Each bar spans whole source bars until it has traded `threshold` units
+        pub fn volume_bars(&mut self, threshold: u64) -> Result<BarSeries, Box<dyn std::error::Error>> {
+            if threshold == 0 {
+                return Err("Volume threshold must be positive".into());
+            }
+            self.aggregate(|src, start, end| src.Volume[start..=end].iter().sum::<u64>() >= threshold)
+        }
+
+        // Each bar spans whole source bars until it has traded `threshold` in notional (close * volume)
+        pub fn dollar_bars(&mut self, threshold: f64) -> Result<BarSeries, Box<dyn std::error::Error>> {
+            if threshold <= 0.0 {
+                return Err("Dollar threshold must be positive".into());
+            }
+            self.aggregate(|src, start, end| {
+                (start..=end).map(|j| src.Close[j] * src.Volume[j] as f64).sum::<f64>() >= threshold
+            })
+        }
+
+        // A trailing partial bar that never met the condition is dropped
+        fn aggregate<F>(&mut self, complete: F) -> Result<BarSeries, Box<dyn std::error::Error>>
+        where
+            F: Fn(&DataFeed, usize, usize) -> bool,
+        {
+            let src = &self.ohlcv;
+            if src.Date.is_empty() {
+                return Err("No data available".into());
+            }
+            let mut series = BarSeries::new();
+            let mut start = 0;
+
+            for end in 0..src.Date.len() {
+                if !complete(src, start, end) {
+                    continue;
+                }
+                let high = src.High[start..=end].iter().cloned().fold(f64::MIN, f64::max);
+                let low = src.Low[start..=end].iter().cloned().fold(f64::MAX, f64::min);
+                let volume = src.Volume[start..=end].iter().sum();
+                series.push(src, start, end, (src.Open[start], high, low, src.Close[end]), volume);
+                start = end + 1;
+            }
+            Ok(series)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::strategy::strategy::Strategy;
+
+        fn feed(bars: &[(f64, f64, f64, f64, u64)]) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for (k, &(o, h, l, c, v)) in bars.iter().enumerate() {
+                ohlcv.Date.push(format!("2024-01-{:02}", k + 1));
+                ohlcv.Open.push(o);
+                ohlcv.High.push(h);
+                ohlcv.Low.push(l);
+                ohlcv.Close.push(c);
+                ohlcv.Adj_Close.push(c);
+                ohlcv.Volume.push(v);
+            }
+            ohlcv
+        }
+
+        // Bars that only matter for their close and volume
+        fn closes(closes: &[f64]) -> DataFeed {
+            let bars: Vec<_> = closes.iter().enumerate().map(|(k, &c)| (c, c + 0.1, c - 0.1, c, 100 * (k as u64 + 1))).collect();
+            feed(&bars)
+        }
+
+        #[test]
+        fn renko_bricks_need_a_full_box_and_two_to_reverse() {
+            let src = closes(&[100.0, 100.99, 101.0, 103.5, 102.0, 101.01, 101.0]);
+            let series = Bars { ohlcv: src }.renko(BrickSize::Fixed(1.0)).unwrap();
+
+            // 100.99 is short of the box; 101.0 completes it exactly
+            assert_eq!(series.bars.Close, vec![101.0, 102.0, 103.0, 101.0]);
+            assert_eq!(series.bars.Open, vec![100.0, 101.0, 102.0, 102.0]);
+            assert_eq!(series.source_end, vec![2, 3, 3, 6]);
+            // 102.0 and 101.01 are within two boxes of the 103 top, so no reversal until 101.0
+            assert_eq!(series.bars.Date[3], "2024-01-07");
+        }
+
+        #[test]
+        fn renko_move_over_several_boxes_builds_one_brick_per_box() {
+            let src = closes(&[100.0, 100.5, 104.2]);
+            let series = Bars { ohlcv: src }.renko(BrickSize::Fixed(1.0)).unwrap();
+            assert_eq!(series.bars.Close, vec![101.0, 102.0, 103.0, 104.0]);
+            // the first brick carries the volume since the last brick, the rest none
+            assert_eq!(series.bars.Volume, vec![500, 0, 0, 0]);
+            assert!(series.source_end.iter().all(|&end| end == 2));
+        }
+
+        #[test]
+        fn aggregated_bars_close_on_reaching_the_threshold() {
+            let src = feed(&[
+                (10.0, 11.0, 10.0, 10.5, 400),
+                (10.5, 12.0, 10.2, 11.8, 600),
+                (11.8, 12.5, 11.0, 12.2, 1000),
+                (12.2, 12.4, 12.0, 12.1, 300),
+                (12.1, 12.3, 12.0, 12.2, 200),
+            ]);
+
+            let volume = Bars { ohlcv: src.get_ohlcv() }.volume_bars(1000).unwrap();
+            assert_eq!(volume.source_start, vec![0, 2]);
+            assert_eq!(volume.source_end, vec![1, 2]);
+            assert_eq!(volume.bars.Volume, vec![1000, 1000]);
+            assert_eq!(volume.bars.Open, vec![10.0, 11.8]);
+            assert_eq!(volume.bars.High, vec![12.0, 12.5]);
+            assert_eq!(volume.bars.Close, vec![11.8, 12.2]);
+
+            // high 12.0 - low 10.0 reaches the 2.0 range exactly on the second bar
+            let range = Bars { ohlcv: src.get_ohlcv() }.range_bars(2.0).unwrap();
+            assert_eq!(range.source_end, vec![1]);
+
+            // 10.5 * 400 + 11.8 * 600 = 11280 falls short, the third bar crosses 20000
+            let dollar = Bars { ohlcv: src.get_ohlcv() }.dollar_bars(20000.0).unwrap();
+            assert_eq!(dollar.source_end, vec![2]);
+            assert!(Bars { ohlcv: src }.dollar_bars(0.0).is_err());
+        }
+
+        #[test]
+        fn execution_feed_maps_bars_to_real_prices() {
+            let src = feed(&[
+                (100.0, 100.2, 99.8, 100.0, 100),
+                (100.1, 100.6, 100.0, 100.5, 100),
+                (100.6, 103.8, 100.5, 103.5, 100),
+                (103.4, 103.6, 102.9, 103.2, 100),
+            ]);
+            let series = Bars { ohlcv: src.get_ohlcv() }.renko(BrickSize::Fixed(1.0)).unwrap();
+            let exec = series.execution_feed(&src);
+
+            assert_eq!(exec.Date, series.bars.Date);
+            // every brick closes at the real close of the bar that completed it
+            assert_eq!(exec.Close, vec![103.5, 103.5, 103.5]);
+            // the first brick opens at the real open of its first bar, later bricks completed
+            // on the same bar at that bar's close
+            assert_eq!(exec.Open, vec![100.1, 103.5, 103.5]);
+            assert_eq!(exec.High[0], 103.8);
+            assert_eq!(exec.Low[0], 100.0);
+
+            // orders on brick signals fill at the real prices, not the brick prices
+            let strategy = Strategy::from_bars(&series, &src).unwrap();
+            let (date, price) = strategy.backtest.fill(0).unwrap();
+            assert_eq!(date, "2024-01-03");
+            assert_eq!(price, 103.5);
+            assert_ne!(price, series.bars.Open[1]);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+pub mod continuous {
+    #![allow(dead_code)]
+    use crate::data_reader::data::data::{day_number, DataFeed};
+    use std::collections::HashMap;
+
+    // One dated futures contract
+    pub struct Contract {
+        pub symbol: String,
+        // last trading day, "YYYY-MM-DD"
+        pub expiry: String,
+        pub data: DataFeed,
+    }
+
+    impl Contract {
+        pub fn read_csv(symbol: &str, expiry: &str, filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+            let mut data = DataFeed::new();
+            data.read_csv(filename)?;
+            Ok(Contract {
+                symbol: symbol.to_string(),
+                expiry: expiry.to_string(),
+                data,
+            })
+        }
+    }
+
+    // How earlier contracts' prices are shifted to line up with later ones at each roll
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Adjustment {
+        // raw prices, with a jump at every roll
+        None,
+        // add the roll gap (new close - old close); keeps point moves, can turn prices negative
+        BackAdjusted,
+        // scale by new close / old close; keeps percentage moves
+        RatioAdjusted,
+    }
+
+    // Switch from one contract to the next at the close of `bar` of the continuous feed.
+    // Prices are as the continuous feed shows them, so they are equal unless it is unadjusted.
+    #[derive(Debug, Clone)]
+    pub struct Roll {
+        pub bar: usize,
+        pub date: String,
+        pub from: String,
+        pub to: String,
+        pub old_price: f64,
+        pub new_price: f64,
+    }
+
+    pub struct ContinuousContract {
+        pub feed: DataFeed,
+        pub rolls: Vec<Roll>,
+        // contract each bar comes from
+        pub symbols: Vec<String>,
+    }
+
+    impl ContinuousContract {
+        // Chains `contracts` in expiry order, holding each until `roll_days` calendar days before
+        // its expiry. The roll happens on the last bar on or before that day that both contracts
+        // trade; the next contract's bars start after it.
+        pub fn build(mut contracts: Vec<Contract>, roll_days: i64, adjustment: Adjustment) -> Result<Self, Box<dyn std::error::Error>> {
+            if contracts.is_empty() {
+                return Err("No contracts to chain".into());
+            }
+            contracts.sort_by(|a, b| a.expiry.cmp(&b.expiry));
+
+            // (contract, first bar, last bar exclusive) per segment and (old close, new close) per roll
+            let mut segments: Vec<(usize, usize, usize)> = Vec::new();
+            let mut gaps: Vec<(f64, f64)> = Vec::new();
+            let mut start = 0;
+            for c in 0..contracts.len() {
+                let data = &contracts[c].data;
+                if start >= data.Date.len() {
+                    return Err(format!("Contract {} has no bars after the previous roll", contracts[c].symbol).into());
+                }
+                if c + 1 == contracts.len() {
+                    segments.push((c, start, data.Date.len()));
+                    break;
+                }
+                let expiry = day_number(&contracts[c].expiry).ok_or("Expiry must be YYYY-MM-DD")?;
+                let next = &contracts[c + 1].data;
+                let next_index: HashMap<&str, usize> = next.Date.iter().enumerate().map(|(i, d)| (d.as_str(), i)).collect();
+                let roll = (start..data.Date.len())
+                    .rev()
+                    .filter(|&i| day_number(&data.Date[i]).is_some_and(|d| d <= expiry - roll_days))
+                    .find_map(|i| next_index.get(data.Date[i].as_str()).map(|&j| (i, j)))
+                    .ok_or_else(|| format!("No common bar to roll {} into {}", contracts[c].symbol, contracts[c + 1].symbol))?;
+                segments.push((c, start, roll.0 + 1));
+                gaps.push((data.Close[roll.0], next.Close[roll.1]));
+                start = roll.1 + 1;
+            }
+
+            // adjustment of each segment, accumulated from the last one back
+            let mut adjust = vec![(0.0, 1.0); segments.len()];
+            for s in (0..gaps.len()).rev() {
+                let (old, new) = gaps[s];
+                let (offset, ratio) = adjust[s + 1];
+                adjust[s] = match adjustment {
+                    Adjustment::None => (0.0, 1.0),
+                    Adjustment::BackAdjusted => (offset + new - old, 1.0),
+                    Adjustment::RatioAdjusted => {
+                        if old <= 0.0 {
+                            return Err("Ratio adjustment needs positive prices".into());
+                        }
+                        (0.0, ratio * new / old)
+                    }
+                };
+            }
+            let apply = |p: f64, (offset, ratio): (f64, f64)| p * ratio + offset;
+
+            let mut feed = DataFeed::new();
+            let mut symbols = Vec::new();
+            let mut rolls = Vec::new();
+            for (s, &(c, first, last)) in segments.iter().enumerate() {
+                let data = &contracts[c].data;
+                for i in first..last {
+                    feed.Date.push(data.Date[i].clone());
+                    feed.Open.push(apply(data.Open[i], adjust[s]));
+                    feed.High.push(apply(data.High[i], adjust[s]));
+                    feed.Low.push(apply(data.Low[i], adjust[s]));
+                    feed.Close.push(apply(data.Close[i], adjust[s]));
+                    feed.Adj_Close.push(apply(data.Adj_Close[i], adjust[s]));
+                    feed.Volume.push(data.Volume[i]);
+                    symbols.push(contracts[c].symbol.clone());
+                }
+                if s < gaps.len() {
+                    let (old, new) = gaps[s];
+                    rolls.push(Roll {
+                        bar: feed.Date.len() - 1,
+                        date: data.Date[last - 1].clone(),
+                        from: contracts[c].symbol.clone(),
+                        to: contracts[c + 1].symbol.clone(),
+                        old_price: apply(old, adjust[s]),
+                        new_price: apply(new, adjust[s + 1]),
+                    });
+                }
+            }
+            Ok(ContinuousContract { feed, rolls, symbols })
+        }
+    }
+}
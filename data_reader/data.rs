@@ -101,8 +101,66 @@ pub mod data {
             Ok(())
         }
 
+        // Bars [start, end) as a new feed
+        pub fn slice(&self, start: usize, end: usize) -> Result<DataFeed, Box<dyn std::error::Error>> {
+            if start >= end || end > self.Date.len() {
+                return Err("Invalid slice range".into());
+            }
+            Ok(DataFeed {
+                Date: self.Date[start..end].to_vec(),
+                Open: self.Open[start..end].to_vec(),
+                High: self.High[start..end].to_vec(),
+                Low: self.Low[start..end].to_vec(),
+                Close: self.Close[start..end].to_vec(),
+                Adj_Close: self.Adj_Close[start..end].to_vec(),
+                Volume: self.Volume[start..end].to_vec(),
+            })
+        }
+
         pub fn get_size(&self) -> Result<i32, Box<dyn std::error::Error>> {
             Ok(self.Date.len().try_into().unwrap())
         }
     }
-}
\ No newline at end of file
+
+    // Days since 1970-01-01 of a "YYYY-MM-DD..." date (Howard Hinnant's days_from_civil);
+    // anything after the day, such as a time, is ignored
+    pub fn day_number(date: &str) -> Option<i64> {
+        let day = date.get(..10)?;
+        let mut parts = day.split('-');
+        let y: i64 = parts.next()?.parse().ok()?;
+        let m: i64 = parts.next()?.parse().ok()?;
+        let d: i64 = parts.next()?.parse().ok()?;
+        if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+            return None;
+        }
+        let y = if m <= 2 { y - 1 } else { y };
+        let era = if y >= 0 { y } else { y - 399 } / 400;
+        let yoe = y - era * 400;
+        let mp = (m + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + d - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        Some(era * 146097 + doe - 719468)
+    }
+
+    // Seconds since 1970-01-01 00:00 of "YYYY-MM-DD", "YYYY-MM-DD HH:MM" or
+    // "YYYY-MM-DD HH:MM:SS" (a "T" may separate the time); a bare date is its midnight.
+    // Fractions of a second and a trailing "Z" are ignored
+    pub fn timestamp(date: &str) -> Option<i64> {
+        let day = day_number(date)?;
+        let time = date[10..].trim_start_matches([' ', 'T']).trim_end_matches('Z');
+        if time.is_empty() {
+            return Some(day * 86400);
+        }
+        let mut parts = time.split(':');
+        let h: i64 = parts.next()?.parse().ok()?;
+        let m: i64 = parts.next()?.parse().ok()?;
+        let s: i64 = match parts.next() {
+            Some(s) => s.split('.').next()?.parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() || !(0..24).contains(&h) || !(0..60).contains(&m) || !(0..61).contains(&s) {
+            return None;
+        }
+        Some(day * 86400 + h * 3600 + m * 60 + s)
+    }
+}
@@ -1 +1,4 @@
-pub mod data;
\ No newline at end of file
+pub mod data;
+pub mod bars;
+pub mod synthetic;
+pub mod continuous;
@@ -0,0 +1,323 @@
+pub mod synthetic {
+    #![allow(dead_code)]
+    use crate::data_reader::data::data::{day_number, DataFeed};
+    use crate::random::random::Rng;
+
+    // Return processes; all parameters are per bar, in log-return terms
+    #[derive(Debug, Clone)]
+    pub enum Model {
+        Gbm { mu: f64, sigma: f64 },
+        // GARCH(1,1): var_t = omega + alpha * eps_{t-1}^2 + beta * var_{t-1}
+        Garch { mu: f64, omega: f64, alpha: f64, beta: f64 },
+        // Merton: GBM plus Poisson(lambda) jumps of size N(jump_mean, jump_std)
+        JumpDiffusion { mu: f64, sigma: f64, lambda: f64, jump_mean: f64, jump_std: f64 },
+        // Markov chain over (mu, sigma) regimes; transition[i][j] is P(i -> j), starts in regime 0
+        RegimeSwitching { regimes: Vec<(f64, f64)>, transition: Vec<Vec<f64>> },
+    }
+
+    // Every bar is built from a random intrabar path of `steps_per_bar` moves pinned to the
+    // bar's return, so High and Low are the path's extremes and always contain Open and Close.
+    // A share `overnight` of each bar's variance is realised as a gap between the previous
+    // close and the open; the close-to-close return does not depend on it.
+    pub struct Generator {
+        pub seed: u64,
+        pub bars: usize,
+        pub start_price: f64,
+        // "YYYY-MM-DD"; one bar per day
+        pub start_date: String,
+        pub steps_per_bar: usize,
+        pub base_volume: f64,
+        // in [0, 1); 0 opens every bar at the previous close
+        pub overnight: f64,
+    }
+
+    impl Generator {
+        pub fn new(seed: u64, bars: usize) -> Self {
+            Generator {
+                seed,
+                bars,
+                start_price: 100.0,
+                start_date: "2020-01-01".to_string(),
+                steps_per_bar: 16,
+                base_volume: 1_000_000.0,
+                overnight: 0.2,
+            }
+        }
+
+        pub fn generate(&self, model: &Model) -> Result<DataFeed, Box<dyn std::error::Error>> {
+            validate(model)?;
+            if !(0.0..1.0).contains(&self.overnight) {
+                return Err("Overnight share must be in [0, 1)".into());
+            }
+            let dates = self.dates()?;
+            let mut rng = Rng::new(self.seed);
+            let mut feed = DataFeed::new();
+            let mut price = self.start_price;
+
+            let mut variance = match model {
+                Model::Garch { omega, alpha, beta, .. } => omega / (1.0 - alpha - beta),
+                _ => 0.0,
+            };
+            let mut regime = 0;
+
+            for date in dates {
+                // (diffusive log return, sigma, jump) of this bar
+                let (diffusive, sigma, jump) = match model {
+                    Model::Gbm { mu, sigma } => (mu - sigma * sigma / 2.0 + sigma * rng.normal(), *sigma, 0.0),
+                    Model::Garch { mu, omega, alpha, beta } => {
+                        let sigma = variance.sqrt();
+                        let shock = sigma * rng.normal();
+                        variance = omega + alpha * shock * shock + beta * variance;
+                        (mu - sigma * sigma / 2.0 + shock, sigma, 0.0)
+                    }
+                    Model::JumpDiffusion { mu, sigma, lambda, jump_mean, jump_std } => {
+                        // compensate the drift so the expected return stays mu
+                        let kappa = (jump_mean + jump_std * jump_std / 2.0).exp() - 1.0;
+                        let jump = (0..poisson(*lambda, &mut rng)).map(|_| jump_mean + jump_std * rng.normal()).sum();
+                        (mu - sigma * sigma / 2.0 - lambda * kappa + sigma * rng.normal(), *sigma, jump)
+                    }
+                    Model::RegimeSwitching { regimes, transition } => {
+                        let (mu, sigma) = regimes[regime];
+                        regime = next_regime(&transition[regime], &mut rng);
+                        (mu - sigma * sigma / 2.0 + sigma * rng.normal(), sigma, 0.0)
+                    }
+                };
+                let (open, high, low, close) = self.bar(price, diffusive, sigma, jump, &mut rng);
+                let move_size = if sigma > 0.0 { ((close / open).ln().abs() / sigma).min(5.0) } else { 0.0 };
+                let volume = self.base_volume * (0.3 * rng.normal()).exp() * (1.0 + move_size);
+
+                feed.Date.push(date);
+                feed.Open.push(open);
+                feed.High.push(high);
+                feed.Low.push(low);
+                feed.Close.push(close);
+                feed.Adj_Close.push(close);
+                feed.Volume.push(volume.round() as u64);
+                price = close;
+            }
+            Ok(feed)
+        }
+
+        // Stationary bootstrap (Politis and Romano): runs of source bars with geometric
+        // lengths of mean `mean_block`, wrapping around the end. Each bar keeps its source
+        // gap, range and body relative to the previous close and its volume.
+        pub fn block_bootstrap(&self, source: &DataFeed, mean_block: f64) -> Result<DataFeed, Box<dyn std::error::Error>> {
+            let n = source.Close.len();
+            if n < 2 {
+                return Err("Not enough data to bootstrap".into());
+            }
+            if mean_block < 1.0 {
+                return Err("Mean block length must be at least one bar".into());
+            }
+            let dates = self.dates()?;
+            let mut rng = Rng::new(self.seed);
+            let mut feed = DataFeed::new();
+            let mut price = self.start_price;
+            // bars 1..n have a previous close to measure the gap from
+            let mut j = 1 + rng.below(n - 1);
+
+            for date in dates {
+                let prev = source.Close[j - 1];
+                let open = price * source.Open[j] / prev;
+                feed.Date.push(date);
+                feed.Open.push(open);
+                feed.High.push(open * source.High[j] / source.Open[j]);
+                feed.Low.push(open * source.Low[j] / source.Open[j]);
+                feed.Close.push(open * source.Close[j] / source.Open[j]);
+                feed.Adj_Close.push(open * source.Close[j] / source.Open[j]);
+                feed.Volume.push(source.Volume[j]);
+                price = *feed.Close.last().unwrap();
+
+                j = if rng.chance(1.0 / mean_block) {
+                    1 + rng.below(n - 1)
+                } else if j + 1 < n {
+                    j + 1
+                } else {
+                    1
+                };
+            }
+            Ok(feed)
+        }
+
+        // The open is the bar's Brownian bridge from `price` to the diffusive return sampled
+        // at `overnight`; from there a bridge of `steps_per_bar` moves ends at that return,
+        // and any jump happens at one random step
+        fn bar(&self, price: f64, diffusive: f64, sigma: f64, jump: f64, rng: &mut Rng) -> (f64, f64, f64, f64) {
+            let gap = self.overnight * diffusive + (self.overnight * (1.0 - self.overnight)).sqrt() * sigma * rng.normal();
+            let open = price * gap.exp();
+            let diffusive = diffusive - gap;
+            let steps = self.steps_per_bar.max(1);
+            let step_sigma = sigma * (1.0 - self.overnight).sqrt() / (steps as f64).sqrt();
+            let mut walk = vec![0.0; steps + 1];
+            for k in 1..=steps {
+                walk[k] = walk[k - 1] + step_sigma * rng.normal();
+            }
+            let jump_step = 1 + rng.below(steps);
+            let (mut high, mut low) = (open, open);
+            let mut close = open;
+            for k in 1..=steps {
+                let t = k as f64 / steps as f64;
+                let mut x = walk[k] - t * (walk[steps] - diffusive);
+                if k >= jump_step {
+                    x += jump;
+                }
+                close = open * x.exp();
+                high = high.max(close);
+                low = low.min(close);
+            }
+            (open, high, low, close)
+        }
+
+        fn dates(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            let start = day_number(&self.start_date).ok_or("Start date must be YYYY-MM-DD")?;
+            Ok((0..self.bars as i64)
+                .map(|k| {
+                    let (y, m, d) = civil_from_days(start + k);
+                    format!("{:04}-{:02}-{:02}", y, m, d)
+                })
+                .collect())
+        }
+    }
+
+    fn validate(model: &Model) -> Result<(), Box<dyn std::error::Error>> {
+        match model {
+            Model::Gbm { sigma, .. } if *sigma < 0.0 => Err("Sigma must not be negative".into()),
+            Model::Garch { omega, alpha, beta, .. } => {
+                if *omega <= 0.0 || *alpha < 0.0 || *beta < 0.0 || alpha + beta >= 1.0 {
+                    return Err("GARCH needs omega > 0, alpha, beta >= 0 and alpha + beta < 1".into());
+                }
+                Ok(())
+            }
+            Model::JumpDiffusion { sigma, lambda, jump_std, .. } if *sigma < 0.0 || *lambda < 0.0 || *jump_std < 0.0 => {
+                Err("Sigma, lambda and jump_std must not be negative".into())
+            }
+            Model::RegimeSwitching { regimes, transition } => {
+                if regimes.is_empty() || transition.len() != regimes.len() {
+                    return Err("Need one transition row per regime".into());
+                }
+                for row in transition {
+                    if row.len() != regimes.len() || row.iter().any(|p| *p < 0.0) || (row.iter().sum::<f64>() - 1.0).abs() > 1e-9 {
+                        return Err("Transition rows must be probabilities summing to one".into());
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Knuth's method; fine for the small per-bar intensities used here
+    fn poisson(lambda: f64, rng: &mut Rng) -> usize {
+        let limit = (-lambda).exp();
+        let mut k = 0;
+        let mut p = rng.next_f64();
+        while p > limit {
+            k += 1;
+            p *= rng.next_f64();
+        }
+        k
+    }
+
+    fn next_regime(row: &[f64], rng: &mut Rng) -> usize {
+        let u = rng.next_f64();
+        let mut cumulative = 0.0;
+        for (j, p) in row.iter().enumerate() {
+            cumulative += p;
+            if u < cumulative {
+                return j;
+            }
+        }
+        row.len() - 1
+    }
+
+    // Inverse of day_number
+    fn civil_from_days(z: i64) -> (i64, i64, i64) {
+        let z = z + 719468;
+        let era = if z >= 0 { z } else { z - 146096 } / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let d = doy - (153 * mp + 2) / 5 + 1;
+        let m = if mp < 10 { mp + 3 } else { mp - 9 };
+        (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const MU: f64 = 0.0005;
+        const SIGMA: f64 = 0.02;
+        const BARS: usize = 20_000;
+
+        fn mean_var(values: &[f64]) -> (f64, f64) {
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+            (mean, var)
+        }
+
+        fn close_returns(feed: &DataFeed, start: f64) -> Vec<f64> {
+            let mut prev = start;
+            feed.Close
+                .iter()
+                .map(|c| {
+                    let r = (c / prev).ln();
+                    prev = *c;
+                    r
+                })
+                .collect()
+        }
+
+        #[test]
+        fn gbm_log_returns_have_the_model_mean_and_variance() {
+            let generator = Generator::new(7, BARS);
+            let feed = generator.generate(&Model::Gbm { mu: MU, sigma: SIGMA }).unwrap();
+            let (mean, var) = mean_var(&close_returns(&feed, generator.start_price));
+
+            // within four standard errors of mu - sigma^2 / 2 and sigma^2
+            let mean_se = SIGMA / (BARS as f64).sqrt();
+            let var_se = SIGMA * SIGMA * (2.0 / BARS as f64).sqrt();
+            assert!((mean - (MU - SIGMA * SIGMA / 2.0)).abs() < 4.0 * mean_se, "mean {}", mean);
+            assert!((var - SIGMA * SIGMA).abs() < 4.0 * var_se, "variance {}", var);
+        }
+
+        #[test]
+        fn opens_gap_by_the_overnight_share_of_variance() {
+            let mut generator = Generator::new(11, BARS);
+            generator.overnight = 0.25;
+            let feed = generator.generate(&Model::Gbm { mu: MU, sigma: SIGMA }).unwrap();
+
+            let gaps: Vec<f64> = (1..BARS).map(|i| (feed.Open[i] / feed.Close[i - 1]).ln()).collect();
+            let (_, gap_var) = mean_var(&gaps);
+            let expected = 0.25 * SIGMA * SIGMA;
+            assert!((gap_var - expected).abs() < 4.0 * expected * (2.0 / BARS as f64).sqrt(), "gap variance {}", gap_var);
+            // the gaps leave the close-to-close variance alone
+            let (_, var) = mean_var(&close_returns(&feed, generator.start_price));
+            assert!((var - SIGMA * SIGMA).abs() < 4.0 * SIGMA * SIGMA * (2.0 / BARS as f64).sqrt(), "variance {}", var);
+
+            for i in 0..BARS {
+                assert!(feed.High[i] >= feed.Open[i].max(feed.Close[i]));
+                assert!(feed.Low[i] <= feed.Open[i].min(feed.Close[i]));
+            }
+
+            generator.overnight = 0.0;
+            let feed = generator.generate(&Model::Gbm { mu: MU, sigma: SIGMA }).unwrap();
+            assert!((1..BARS).all(|i| feed.Open[i] == feed.Close[i - 1]));
+
+            generator.overnight = 1.0;
+            assert!(generator.generate(&Model::Gbm { mu: MU, sigma: SIGMA }).is_err());
+        }
+
+        #[test]
+        fn same_seed_gives_the_same_bars() {
+            let generator = Generator::new(3, 200);
+            let model = Model::Garch { mu: 0.0, omega: 1e-5, alpha: 0.1, beta: 0.85 };
+            let a = generator.generate(&model).unwrap();
+            let b = generator.generate(&model).unwrap();
+            assert_eq!(a.Open, b.Open);
+            assert_eq!(a.Close, b.Close);
+            assert_eq!(a.Volume, b.Volume);
+        }
+    }
+}
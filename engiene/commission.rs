@@ -0,0 +1,301 @@
+pub mod commission {
+    #![allow(dead_code)]
+    use crate::engiene::engiene::backtest::{PramVal, Prams};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Liquidity {
+        // resting order filled by someone else, e.g. a take-profit limit
+        Maker,
+        // order that crosses the book, e.g. a market or stop order
+        Taker,
+    }
+
+    impl Liquidity {
+        pub fn from_name(name: &str) -> Option<Liquidity> {
+            match name.to_lowercase().as_str() {
+                "maker" => Some(Liquidity::Maker),
+                "taker" => Some(Liquidity::Taker),
+                _ => None,
+            }
+        }
+    }
+
+    // What a fee schedule sees of one fill. `traded` is the quantity filled before this
+    // one, for schedules that get cheaper with volume.
+    pub struct FillInfo {
+        pub price: f64,
+        pub quantity: f64,
+        // contract multiplier, 1.0 for spot
+        pub multiplier: f64,
+        pub liquidity: Liquidity,
+        pub traded: f64,
+    }
+
+    impl FillInfo {
+        pub fn notional(&self) -> f64 {
+            self.price * self.quantity * self.multiplier
+        }
+    }
+
+    // Fee for one fill; negative for a rebate
+    pub trait CommissionModel: Send + Sync {
+        fn commission(&self, fill: &FillInfo) -> f64;
+    }
+
+    // Fraction of notional
+    pub struct Percent(pub f64);
+
+    impl CommissionModel for Percent {
+        fn commission(&self, fill: &FillInfo) -> f64 {
+            self.0 * fill.notional()
+        }
+    }
+
+    // `rate` per share, at least `minimum` per fill and at most `max_percent` of notional
+    // (0.0 for no cap); the cap wins over the minimum, as with most equity brokers
+    pub struct PerShare {
+        pub rate: f64,
+        pub minimum: f64,
+        pub max_percent: f64,
+    }
+
+    fn capped(fee: f64, minimum: f64, max_percent: f64, fill: &FillInfo) -> f64 {
+        let fee = fee.max(minimum);
+        if max_percent > 0.0 {
+            fee.min(max_percent * fill.notional())
+        } else {
+            fee
+        }
+    }
+
+    impl CommissionModel for PerShare {
+        fn commission(&self, fill: &FillInfo) -> f64 {
+            capped(self.rate * fill.quantity, self.minimum, self.max_percent, fill)
+        }
+    }
+
+    // Per-share rate that falls with the quantity already traded. `tiers` holds
+    // (quantity threshold, rate) pairs; the last tier whose threshold has been reached applies.
+    pub struct Tiered {
+        pub tiers: Vec<(f64, f64)>,
+        pub minimum: f64,
+        pub max_percent: f64,
+    }
+
+    impl CommissionModel for Tiered {
+        fn commission(&self, fill: &FillInfo) -> f64 {
+            let rate = self
+                .tiers
+                .iter()
+                .rev()
+                .find(|(threshold, _)| *threshold <= fill.traded)
+                .or(self.tiers.first())
+                .map_or(0.0, |(_, rate)| *rate);
+            capped(rate * fill.quantity, self.minimum, self.max_percent, fill)
+        }
+    }
+
+    // Fractions of notional by liquidity; a negative maker rate is a rebate
+    pub struct MakerTaker {
+        pub maker: f64,
+        pub taker: f64,
+    }
+
+    impl CommissionModel for MakerTaker {
+        fn commission(&self, fill: &FillInfo) -> f64 {
+            let rate = match fill.liquidity {
+                Liquidity::Maker => self.maker,
+                Liquidity::Taker => self.taker,
+            };
+            rate * fill.notional()
+        }
+    }
+
+    // Flat fee per contract
+    pub struct PerContract(pub f64);
+
+    impl CommissionModel for PerContract {
+        fn commission(&self, fill: &FillInfo) -> f64 {
+            self.0 * fill.quantity
+        }
+    }
+
+    pub(crate) fn get_double(prams: &Prams, key: &str) -> f64 {
+        match prams.data.get(key) {
+            Some(PramVal::Double(v)) => *v,
+            Some(PramVal::Int(v)) => *v as f64,
+            _ => 0.0,
+        }
+    }
+
+    // "threshold:rate" pairs separated by commas, e.g. "0:0.005,500000:0.003"
+    fn parse_tiers(text: &str) -> Result<Vec<(f64, f64)>, Box<dyn std::error::Error>> {
+        let mut tiers = Vec::new();
+        for pair in text.split(',') {
+            let (threshold, rate) = pair.split_once(':').ok_or("commission_tiers entries must be threshold:rate")?;
+            tiers.push((threshold.trim().parse::<f64>()?, rate.trim().parse::<f64>()?));
+        }
+        if tiers.windows(2).any(|w| w[1].0 <= w[0].0) {
+            return Err("commission_tiers thresholds must increase".into());
+        }
+        Ok(tiers)
+    }
+
+    // Model named by "commission_model" ("percent", "per_share", "tiered", "maker_taker" or
+    // "per_contract"), with rates from "commission", "commission_min", "commission_max_pct",
+    // "commission_tiers", "maker_fee" and "taker_fee". Without "commission_model",
+    // "commission" alone selects Percent. None when the parameters name no model.
+    pub fn from_params(prams: &Prams) -> Result<Option<Box<dyn CommissionModel>>, Box<dyn std::error::Error>> {
+        let name = match prams.data.get("commission_model") {
+            Some(PramVal::Str(name)) => name.to_lowercase(),
+            Some(_) => return Err("commission_model must be a string".into()),
+            None if prams.data.contains_key("commission") => "percent".to_string(),
+            None => return Ok(None),
+        };
+        let rate = get_double(prams, "commission");
+        let model: Box<dyn CommissionModel> = match name.as_str() {
+            "percent" => Box::new(Percent(rate)),
+            "per_share" => Box::new(PerShare {
+                rate,
+                minimum: get_double(prams, "commission_min"),
+                max_percent: get_double(prams, "commission_max_pct"),
+            }),
+            "tiered" => {
+                let tiers = match prams.data.get("commission_tiers") {
+                    Some(PramVal::Str(text)) => parse_tiers(text)?,
+                    _ => return Err("tiered commission needs commission_tiers".into()),
+                };
+                Box::new(Tiered {
+                    tiers,
+                    minimum: get_double(prams, "commission_min"),
+                    max_percent: get_double(prams, "commission_max_pct"),
+                })
+            }
+            "maker_taker" => Box::new(MakerTaker {
+                maker: get_double(prams, "maker_fee"),
+                taker: get_double(prams, "taker_fee"),
+            }),
+            "per_contract" => Box::new(PerContract(rate)),
+            _ => return Err(format!("Unknown commission_model {}", name).into()),
+        };
+        Ok(Some(model))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::collections::HashMap;
+
+        fn fill(price: f64, quantity: f64) -> FillInfo {
+            FillInfo {
+                price,
+                quantity,
+                multiplier: 1.0,
+                liquidity: Liquidity::Taker,
+                traded: 0.0,
+            }
+        }
+
+        fn prams(pairs: &[(&str, PramVal)]) -> Prams {
+            Prams {
+                data: pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect::<HashMap<_, _>>(),
+            }
+        }
+
+        #[test]
+        fn per_share_has_a_minimum_and_a_cap() {
+            let model = PerShare { rate: 0.005, minimum: 1.0, max_percent: 0.01 };
+            // 100 shares cost 0.50, raised to the minimum
+            assert!((model.commission(&fill(50.0, 100.0)) - 1.0).abs() < 1e-12);
+            assert!((model.commission(&fill(50.0, 1000.0)) - 5.0).abs() < 1e-12);
+            // 1% of a 10.0 notional is below the minimum, and the cap wins
+            assert!((model.commission(&fill(0.1, 100.0)) - 0.1).abs() < 1e-12);
+        }
+
+        #[test]
+        fn tiered_rate_follows_the_quantity_already_traded() {
+            let model = Tiered { tiers: vec![(0.0, 0.01), (1000.0, 0.005), (5000.0, 0.002)], minimum: 0.0, max_percent: 0.0 };
+            let mut info = fill(10.0, 100.0);
+            for (traded, fee) in [(0.0, 1.0), (999.0, 1.0), (1000.0, 0.5), (5000.0, 0.2)] {
+                info.traded = traded;
+                assert!((model.commission(&info) - fee).abs() < 1e-12);
+            }
+        }
+
+        #[test]
+        fn maker_rebate_is_a_negative_fee() {
+            let model = MakerTaker { maker: -0.0002, taker: 0.0005 };
+            let mut info = fill(100.0, 10.0);
+            assert!((model.commission(&info) - 0.5).abs() < 1e-12);
+            info.liquidity = Liquidity::Maker;
+            assert!((model.commission(&info) + 0.2).abs() < 1e-12);
+        }
+
+        #[test]
+        fn per_contract_fee_ignores_the_multiplier_that_notional_fees_scale_with() {
+            let mut info = fill(4000.0, 3.0);
+            info.multiplier = 50.0;
+            assert!((PerContract(2.25).commission(&info) - 6.75).abs() < 1e-12);
+            assert!((Percent(0.0001).commission(&info) - 60.0).abs() < 1e-9);
+        }
+
+        #[test]
+        fn models_are_read_from_params() {
+            let info = fill(20.0, 100.0);
+            assert!(from_params(&prams(&[])).unwrap().is_none());
+
+            let model = from_params(&prams(&[("commission", PramVal::Double(0.001))])).unwrap().unwrap();
+            assert!((model.commission(&info) - 2.0).abs() < 1e-12);
+
+            let model = from_params(&prams(&[
+                ("commission_model", PramVal::Str("per_share".to_string())),
+                ("commission", PramVal::Double(0.005)),
+                ("commission_min", PramVal::Int(1)),
+            ]))
+            .unwrap()
+            .unwrap();
+            assert!((model.commission(&info) - 1.0).abs() < 1e-12);
+
+            let model = from_params(&prams(&[
+                ("commission_model", PramVal::Str("Tiered".to_string())),
+                ("commission_tiers", PramVal::Str("0:0.01, 50:0.002".to_string())),
+            ]))
+            .unwrap()
+            .unwrap();
+            assert!((model.commission(&info) - 1.0).abs() < 1e-12);
+
+            let model = from_params(&prams(&[
+                ("commission_model", PramVal::Str("maker_taker".to_string())),
+                ("maker_fee", PramVal::Double(-0.0001)),
+                ("taker_fee", PramVal::Double(0.0004)),
+            ]))
+            .unwrap()
+            .unwrap();
+            assert!((model.commission(&info) - 0.8).abs() < 1e-12);
+
+            let model = from_params(&prams(&[
+                ("commission_model", PramVal::Str("per_contract".to_string())),
+                ("commission", PramVal::Double(1.5)),
+            ]))
+            .unwrap()
+            .unwrap();
+            assert!((model.commission(&info) - 150.0).abs() < 1e-12);
+
+            for bad in [
+                vec![("commission_model", PramVal::Str("flat".to_string()))],
+                vec![("commission_model", PramVal::Double(1.0))],
+                vec![("commission_model", PramVal::Str("tiered".to_string()))],
+                vec![
+                    ("commission_model", PramVal::Str("tiered".to_string())),
+                    ("commission_tiers", PramVal::Str("100:0.01,0:0.02".to_string())),
+                ],
+                vec![
+                    ("commission_model", PramVal::Str("tiered".to_string())),
+                    ("commission_tiers", PramVal::Str("0-0.01".to_string())),
+                ],
+            ] {
+                assert!(from_params(&prams(&bad)).is_err());
+            }
+        }
+    }
+}
@@ -1,26 +1,169 @@
 pub mod backtest {
     #![allow(dead_code)]
-    use crate::data_reader::data::data::DataFeed;
+    use crate::data_reader::data::data::{day_number, DataFeed};
+    use crate::indicators::stats::sharpe_ratio;
+    use crate::engiene::slippage::slippage::{self, FixedBps, SlippageModel};
+    use crate::engiene::commission::commission::{self, CommissionModel, FillInfo, Liquidity, Percent};
+    use crate::engiene::shorting::shorting::{read_series, BorrowRate, ShortCosts};
+    use crate::engiene::funding::funding::FundingRates;
+    use crate::engiene::instrument::instrument::Instrument;
+    use crate::data_reader::continuous::continuous::Roll;
     use std::collections::HashMap;
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, PartialEq)]
     pub enum PramVal {
         Double(f64),
         Int(i32),
         Bool(bool),
+        Str(String),
     }
 
+    impl std::fmt::Display for PramVal {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                PramVal::Double(v) => write!(f, "{}", v),
+                PramVal::Int(v) => write!(f, "{}", v),
+                PramVal::Bool(v) => write!(f, "{}", v),
+                PramVal::Str(v) => write!(f, "{}", v),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
     pub struct Prams {
         pub data: HashMap<String, PramVal>,
     }
+
+    // Price an order from a signal on bar i is filled at
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum FillModel {
+        // close of bar i
+        SignalClose,
+        // open of bar i + 1
+        NextOpen,
+        // (O + H + L + C) / 4 of bar i + 1, the bar's average price without intrabar volume
+        BarVwap,
+        // (H + L + C) / 3 of bar i + 1
+        TypicalPrice,
+    }
+
+    impl FillModel {
+        pub fn from_name(name: &str) -> Option<FillModel> {
+            match name.to_lowercase().as_str() {
+                "signal_close" | "close" => Some(FillModel::SignalClose),
+                "next_open" | "open" => Some(FillModel::NextOpen),
+                "vwap" | "bar_vwap" => Some(FillModel::BarVwap),
+                "typical" | "typical_price" => Some(FillModel::TypicalPrice),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum BracketExit {
+        Stop,
+        Target,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Side {
+        Buy,
+        Sell,
+        Close,
+        // forced close after equity fell to the maintenance margin
+        Liquidate,
+    }
+
+    // One order as submitted; with a participation limit it may fill over several bars.
+    // An order is cancelled, keeping what it filled, when the next order is submitted.
+    #[derive(Debug, Clone)]
+    pub struct Order {
+        pub date: String,
+        pub side: Side,
+        pub requested: f64,
+        pub filled: f64,
+    }
+
+    impl Order {
+        pub fn unfilled(&self) -> f64 {
+            (self.requested - self.filled).max(0.0)
+        }
+
+        pub fn fill_ratio(&self) -> f64 {
+            if self.requested <= 0.0 {
+                return 0.0;
+            }
+            self.filled / self.requested
+        }
+    }
+
+    // Order refused before it reached the book
+    #[derive(Debug, Clone)]
+    pub struct Rejection {
+        pub date: String,
+        pub side: Side,
+        pub price: f64,
+        pub quantity: f64,
+        pub reason: String,
+    }
+
+    // One round trip, from the order that opened a position to the one that flattened or
+    // reversed it. Equity is taken before the opening order so entry costs are included.
+    // `order` indexes the opening order in Backtest::orders; its requested and filled
+    // quantities are copied in when the trade closes, after any remainder was worked.
+    #[derive(Debug, Clone)]
+    pub struct Trade {
+        pub entry_date: String,
+        pub exit_date: String,
+        pub is_long: bool,
+        pub entry_equity: f64,
+        pub exit_equity: f64,
+        pub liquidated: bool,
+        pub order: usize,
+        pub requested: f64,
+        pub filled: f64,
+    }
+
+    impl Trade {
+        pub fn unfilled(&self) -> f64 {
+            (self.requested - self.filled).max(0.0)
+        }
+
+        pub fn fill_ratio(&self) -> f64 {
+            if self.requested <= 0.0 {
+                return 0.0;
+            }
+            self.filled / self.requested
+        }
+
+        pub fn pnl(&self) -> f64 {
+            self.exit_equity - self.entry_equity
+        }
+
+        pub fn ret(&self) -> f64 {
+            if self.entry_equity == 0.0 {
+                return 0.0;
+            }
+            self.exit_equity / self.entry_equity - 1.0
+        }
+    }
+
     pub struct Backtest {
         pub ohlcv: DataFeed,
+        // real prices to fill at when `ohlcv` holds synthetic bars (Heikin-Ashi, Renko, ...)
+        pub execution: Option<DataFeed>,
         pub balance: f64,
         pub init_balance: f64,
         pub final_balance: f64,
         pub commission: f64,
+        pub commission_model: Box<dyn CommissionModel>,
+        // liquidity orders are charged as, unless an exit says otherwise
+        pub liquidity: Liquidity,
+        // quantity filled so far, for volume-tiered commissions
+        pub traded: f64,
         pub total_commission: f64,
         pub slippage: f64,
+        pub slippage_model: Box<dyn SlippageModel>,
         pub total_slippage: f64,
         pub risk: f64,
         pub position: bool,
@@ -32,7 +175,52 @@ pub mod backtest {
         pub sizer: f64,
         pub long_entry_price: f64,
         pub short_entry_price: f64,
-
+        // marked-to-market equity, one point per bar marked by the strategy
+        pub equity: Vec<f64>,
+        pub trades: usize,
+        // closed round trips
+        pub ledger: Vec<Trade>,
+        open_trade: Option<Trade>,
+        pub fill_model: FillModel,
+        // stop-loss and take-profit as fractions of the entry price; 0.0 disables each
+        pub stop_loss: f64,
+        pub take_profit: f64,
+        // margin as fractions of notional and the largest notional / equity; 0.0 disables each
+        pub initial_margin: f64,
+        pub maintenance_margin: f64,
+        pub max_leverage: f64,
+        pub margin_calls: usize,
+        in_margin_call: bool,
+        pub short_costs: ShortCosts,
+        pub total_borrow: f64,
+        pub dividends_paid: f64,
+        pub dividends_received: f64,
+        // (day number, day, long quantity, short quantity, close) at the end of the last bar
+        carry: Option<(i64, String, f64, f64, f64)>,
+        pub funding: FundingRates,
+        pub funding_paid: f64,
+        pub funding_received: f64,
+        // (date, net quantity, close) at the end of the last bar
+        funding_carry: Option<(String, f64, f64)>,
+        // largest share of a bar's volume one bar's fills may take; 0.0 means no limit
+        pub participation: f64,
+        // every order submitted, with requested and filled quantity
+        pub orders: Vec<Order>,
+        // orders the instrument's trading rules refused
+        pub rejections: Vec<Rejection>,
+        // index into `orders` of the order still working its remainder
+        pending: Option<usize>,
+        // bar orders currently fill on, and the volume already taken from it
+        bar: Option<usize>,
+        bar_filled: f64,
+        pub instrument: Instrument,
+        // contract rolls of a continuous futures feed, at the close of their bar
+        pub rolls: Vec<Roll>,
+        // charged per contract on every roll, on top of the commission and slippage of both legs
+        pub roll_cost: f64,
+        pub total_roll_cost: f64,
+        pub rolls_done: usize,
+        pub verbose: bool,
     }
 
 
@@ -40,10 +228,14 @@ pub mod backtest {
         pub fn new(data: DataFeed) -> Self {
             Backtest {
                 ohlcv: data,
+                execution: None,
                 balance: 100000.0,
                 init_balance: 0.0,
                 final_balance: 0.0,
                 commission: 0.0,
+                commission_model: Box::new(Percent(0.0)),
+                liquidity: Liquidity::Taker,
+                traded: 0.0,
                 total_commission: 0.0,
                 risk: 0.0,
                 position: false,
@@ -54,9 +246,43 @@ pub mod backtest {
                 short_quantity: 0.0,
                 sizer: 0.0,
                 slippage: 0.0,
+                slippage_model: Box::new(FixedBps(0.0)),
                 total_slippage: 0.0,
                 long_entry_price: 0.0,
                 short_entry_price: 0.0,
+                equity: Vec::new(),
+                trades: 0,
+                ledger: Vec::new(),
+                open_trade: None,
+                fill_model: FillModel::NextOpen,
+                stop_loss: 0.0,
+                take_profit: 0.0,
+                initial_margin: 0.0,
+                maintenance_margin: 0.0,
+                max_leverage: 0.0,
+                margin_calls: 0,
+                in_margin_call: false,
+                short_costs: ShortCosts::new(),
+                total_borrow: 0.0,
+                dividends_paid: 0.0,
+                dividends_received: 0.0,
+                carry: None,
+                funding: FundingRates::new(),
+                funding_paid: 0.0,
+                funding_received: 0.0,
+                funding_carry: None,
+                participation: 0.0,
+                rejections: Vec::new(),
+                orders: Vec::new(),
+                pending: None,
+                bar: None,
+                bar_filled: 0.0,
+                instrument: Instrument::spot(""),
+                rolls: Vec::new(),
+                roll_cost: 0.0,
+                total_roll_cost: 0.0,
+                rolls_done: 0,
+                verbose: true,
             }
         }
 
@@ -65,28 +291,239 @@ pub mod backtest {
             self.init_balance = cash;
         }
 
+        // Fraction of notional; shorthand for Percent
         pub fn set_comission(&mut self, commission: f64) {
             self.commission = commission;
+            self.commission_model = Box::new(Percent(commission));
+        }
+
+        pub fn set_commission_model(&mut self, model: Box<dyn CommissionModel>) {
+            self.commission_model = model;
         }
 
+        // Flat fraction of the price; shorthand for FixedBps
         pub fn set_slippage(&mut self, slippage: f64) {
             self.slippage = slippage;
+            self.slippage_model = Box::new(FixedBps(slippage * 10_000.0));
+        }
+
+        pub fn set_slippage_model(&mut self, model: Box<dyn SlippageModel>) {
+            self.slippage_model = model;
         }
 
         pub fn set_sizer(&mut self, sizer: f64) {
             self.sizer = sizer;
         }
 
+        pub fn set_fill_model(&mut self, fill_model: FillModel) {
+            self.fill_model = fill_model;
+        }
+
+        pub fn set_margin(&mut self, initial: f64, maintenance: f64) -> Result<(), Box<dyn std::error::Error>> {
+            if initial < 0.0 || !(0.0..1.0).contains(&maintenance) || (initial > 0.0 && maintenance > initial) {
+                return Err("Margins must satisfy 0 <= maintenance <= initial and maintenance < 1".into());
+            }
+            self.initial_margin = initial;
+            self.maintenance_margin = maintenance;
+            Ok(())
+        }
+
+        pub fn set_max_leverage(&mut self, leverage: f64) {
+            self.max_leverage = leverage;
+        }
+
+        // Takes the instrument's margins when it sets them
+        pub fn set_instrument(&mut self, instrument: Instrument) -> Result<(), Box<dyn std::error::Error>> {
+            instrument.validate()?;
+            if instrument.initial_margin > 0.0 || instrument.maintenance_margin > 0.0 {
+                self.set_margin(instrument.initial_margin, instrument.maintenance_margin)?;
+            }
+            self.instrument = instrument;
+            Ok(())
+        }
+
+        pub fn set_rolls(&mut self, rolls: Vec<Roll>, roll_cost: f64) {
+            self.rolls = rolls;
+            self.roll_cost = roll_cost;
+        }
+
+        pub fn set_participation(&mut self, participation: f64) {
+            self.participation = participation;
+        }
+
+        pub fn set_execution(&mut self, feed: DataFeed) -> Result<(), Box<dyn std::error::Error>> {
+            if feed.Date.len() != self.ohlcv.Date.len() {
+                return Err("Execution feed is not aligned with the bar feed".into());
+            }
+            self.execution = Some(feed);
+            Ok(())
+        }
+
+        // Prices orders are filled at: the execution feed when set, otherwise the bars themselves
+        pub fn execution_ohlcv(&self) -> DataFeed {
+            match &self.execution {
+                Some(feed) => feed.get_ohlcv(),
+                None => self.ohlcv.get_ohlcv(),
+            }
+        }
+
+        fn exec(&self) -> &DataFeed {
+            self.execution.as_ref().unwrap_or(&self.ohlcv)
+        }
+
+        // Bar an order placed on a signal from `signal_bar` fills on
+        pub fn fill_bar(&self, signal_bar: usize) -> usize {
+            match self.fill_model {
+                FillModel::SignalClose => signal_bar,
+                _ => signal_bar + 1,
+            }
+        }
+
+        // (date, price) of an order placed on a signal from `signal_bar`; None when the
+        // fill bar is past the end of the data
+        pub fn fill(&self, signal_bar: usize) -> Option<(String, f64)> {
+            let feed = self.exec();
+            let bar = self.fill_bar(signal_bar);
+            if bar >= feed.Date.len() {
+                return None;
+            }
+            Some((feed.Date[bar].clone(), self.fill_price(bar)))
+        }
+
+        // Price the fill model fills at on `bar`
+        fn fill_price(&self, bar: usize) -> f64 {
+            let feed = self.exec();
+            match self.fill_model {
+                FillModel::SignalClose => feed.Close[bar],
+                FillModel::NextOpen => feed.Open[bar],
+                FillModel::BarVwap => (feed.Open[bar] + feed.High[bar] + feed.Low[bar] + feed.Close[bar]) / 4.0,
+                FillModel::TypicalPrice => (feed.High[bar] + feed.Low[bar] + feed.Close[bar]) / 3.0,
+            }
+        }
+
+        // Which of a stop and a target a position held through `bar` reaches first, and the
+        // fill price. Price is taken to travel O->H->L->C when the open is nearer the high and
+        // O->L->H->C otherwise; a level gapped through at the open fills at the open.
+        pub fn intrabar_exit(&self, bar: usize, is_long: bool, stop: f64, target: f64) -> Option<(BracketExit, f64)> {
+            let feed = self.exec();
+            let (open, high, low) = (feed.Open[bar], feed.High[bar], feed.Low[bar]);
+            let stop_hit = |p: f64| if is_long { p <= stop } else { p >= stop };
+            let target_hit = |p: f64| if is_long { p >= target } else { p <= target };
+
+            if stop_hit(open) {
+                return Some((BracketExit::Stop, open));
+            }
+            if target_hit(open) {
+                return Some((BracketExit::Target, open));
+            }
+            let path = if high - open <= open - low { [high, low] } else { [low, high] };
+            for extreme in path {
+                if stop_hit(extreme) {
+                    return Some((BracketExit::Stop, stop));
+                }
+                if target_hit(extreme) {
+                    return Some((BracketExit::Target, target));
+                }
+            }
+            None
+        }
+
+        // Closes the open position at whichever of `stop` and `target` triggers first on `bar`
+        pub fn bracket(&mut self, bar: usize, stop: f64, target: f64) -> Result<Option<BracketExit>, Box<dyn std::error::Error>> {
+            if !self.position {
+                return Ok(None);
+            }
+            let is_long = self.is_long;
+            let exit = self.intrabar_exit(bar, is_long, stop, target);
+            if let Some((kind, price)) = exit {
+                let date = self.exec().Date[bar].clone();
+                let quantity = if is_long { self.long_quantity } else { self.short_quantity };
+                // a target rests as a limit order and adds liquidity; a stop takes it
+                let liquidity = self.liquidity;
+                if kind == BracketExit::Target {
+                    self.liquidity = Liquidity::Maker;
+                }
+                let result = self.close(&date, &price, quantity);
+                self.liquidity = liquidity;
+                result?;
+            }
+            Ok(exit.map(|(kind, _)| kind))
+        }
+
+        // Brackets the open position on `bar` at `stop_loss` and `take_profit` from its entry price
+        pub fn apply_bracket(&mut self, bar: usize) -> Result<Option<BracketExit>, Box<dyn std::error::Error>> {
+            if !self.position || (self.stop_loss <= 0.0 && self.take_profit <= 0.0) {
+                return Ok(None);
+            }
+            // a disabled level is put where price can never reach it
+            let (stop, target) = if self.is_long {
+                let entry = self.long_entry_price;
+                (
+                    if self.stop_loss > 0.0 { entry * (1.0 - self.stop_loss) } else { 0.0 },
+                    if self.take_profit > 0.0 { entry * (1.0 + self.take_profit) } else { f64::INFINITY },
+                )
+            } else {
+                let entry = self.short_entry_price;
+                (
+                    if self.stop_loss > 0.0 { entry * (1.0 + self.stop_loss) } else { f64::INFINITY },
+                    if self.take_profit > 0.0 { entry * (1.0 - self.take_profit) } else { 0.0 },
+                )
+            };
+            self.bracket(bar, stop, target)
+        }
+
         pub fn set_params(&mut self, prams: Prams) -> Result<(), Box<dyn std::error::Error>> {
             for (key, val) in &prams.data {
                 match (key.as_str(), val) {
                     ("balance", PramVal::Double(v)) => self.set_balance(*v),
-                    ("commission", PramVal::Double(v)) => self.set_comission(*v),
+                    ("commission", PramVal::Double(v)) => self.commission = *v,
+                    ("liquidity", PramVal::Str(v)) => {
+                        self.liquidity = Liquidity::from_name(v).ok_or("Unknown liquidity")?
+                    }
                     ("slippage", PramVal::Double(v)) => self.set_slippage(*v),
                     ("sizer", PramVal::Double(v)) => self.set_sizer(*v),
+                    ("fill_model", PramVal::Str(v)) => {
+                        self.set_fill_model(FillModel::from_name(v).ok_or("Unknown fill_model")?)
+                    }
+                    ("participation", PramVal::Double(v)) => self.set_participation(*v),
+                    ("stop_loss", PramVal::Double(v)) => self.stop_loss = *v,
+                    ("take_profit", PramVal::Double(v)) => self.take_profit = *v,
+                    ("max_leverage", PramVal::Double(v)) => self.set_max_leverage(*v),
+                    ("borrow_rate", PramVal::Double(v)) => self.short_costs.borrow = BorrowRate::Fixed(*v),
+                    ("borrow_file", PramVal::Str(v)) => self.short_costs.borrow = BorrowRate::Schedule(read_series(v)?),
+                    ("locate_file", PramVal::Str(v)) => self.short_costs.locates = Some(read_series(v)?),
+                    ("dividend_file", PramVal::Str(v)) => self.short_costs.dividends = read_series(v)?,
+                    ("funding_file", PramVal::Str(v)) => self.funding = FundingRates::read_csv(v)?,
+                    ("multiplier", PramVal::Double(v)) if *v > 0.0 => self.instrument.multiplier = *v,
+                    ("multiplier", PramVal::Double(_)) => return Err("multiplier must be positive".into()),
+                    ("tick_size", PramVal::Double(v)) => self.instrument.tick_size = *v,
+                    ("lot_size", PramVal::Double(v)) => self.instrument.lot_size = *v,
+                    ("min_quantity", PramVal::Double(v)) => self.instrument.min_quantity = *v,
+                    ("min_notional", PramVal::Double(v)) => self.instrument.min_notional = *v,
+                    ("fractional", PramVal::Bool(v)) => self.instrument.fractional = *v,
+                    ("expiry", PramVal::Str(v)) => self.instrument.set_expiry(v),
+                    ("roll_cost", PramVal::Double(v)) => self.roll_cost = *v,
+                    ("verbose", PramVal::Bool(v)) => self.verbose = *v,
                     _ => {}
                 }
             }
+            // the two margins are validated together
+            let initial = match prams.data.get("initial_margin") {
+                Some(PramVal::Double(v)) => *v,
+                _ => self.initial_margin,
+            };
+            let maintenance = match prams.data.get("maintenance_margin") {
+                Some(PramVal::Double(v)) => *v,
+                _ => self.maintenance_margin,
+            };
+            self.set_margin(initial, maintenance)?;
+            self.instrument.validate()?;
+            if let Some(model) = commission::from_params(&prams)? {
+                self.commission_model = model;
+            }
+            if let Some(model) = slippage::from_params(&prams, self.instrument.tick_size)? {
+                self.slippage_model = model;
+            }
             Ok(())
         }
 
@@ -94,49 +531,190 @@ pub mod backtest {
             if price <= 0.0 || quantity <= 0.0 {
                 return Err("Invalid price or quantity".into());
             }
-            let comm = self.commission * price * quantity;
+            let comm = self.commission_model.commission(&FillInfo {
+                price,
+                quantity,
+                multiplier: self.instrument.multiplier,
+                liquidity: self.liquidity,
+                traded: self.traded,
+            });
+            self.traded += quantity;
             self.total_commission += comm;
             Ok(comm)
         }
 
+        // Per-unit slippage from the model, on the current bar (the last bar when none is set)
         fn calculate_slippage(&mut self, price: f64, quantity: f64) -> f64 {
             if price <= 0.0 || quantity <= 0.0 {
                 return 0.0;
             }
-            let slip = price * self.slippage * quantity;
-            self.total_slippage += slip;
+            let feed = self.exec();
+            let bar = self.bar.unwrap_or(feed.Date.len().saturating_sub(1));
+            let slip = self.slippage_model.slippage(feed, bar, price, quantity, self.instrument.tick_size).max(0.0);
+            self.total_slippage += self.instrument.notional(slip, quantity);
             slip
         }
 
         pub fn log(&mut self, record: String) {
-            println!("{:?}", record);
+            if self.verbose {
+                println!("{:?}", record);
+            }
+        }
+
+        // Cash plus open positions valued at `price`
+        pub fn equity_value(&self, price: f64) -> f64 {
+            self.balance + self.instrument.notional(price, self.long_quantity - self.short_quantity)
+        }
+
+        pub fn mark(&mut self, price: f64) {
+            let value = self.equity_value(price);
+            self.equity.push(value);
+        }
+
+        pub fn returns(&self) -> Vec<f64> {
+            self.equity
+                .windows(2)
+                .map(|w| if w[0] != 0.0 { w[1] / w[0] - 1.0 } else { 0.0 })
+                .collect()
+        }
+
+        pub fn total_return(&self) -> f64 {
+            if self.init_balance == 0.0 {
+                return 0.0;
+            }
+            (self.final_balance - self.init_balance) / self.init_balance
+        }
+
+        // Annualised Sharpe ratio of the per-bar equity returns, zero risk-free rate
+        pub fn sharpe(&self, periods_per_year: f64) -> f64 {
+            sharpe_ratio(&self.returns(), periods_per_year)
+        }
+
+        // Largest peak-to-trough fall of the equity curve as a fraction of the peak
+        pub fn max_drawdown(&self) -> f64 {
+            let mut peak = f64::MIN;
+            let mut max_dd = 0.0;
+            for value in &self.equity {
+                peak = peak.max(*value);
+                if peak > 0.0 {
+                    max_dd = f64::max(max_dd, (peak - value) / peak);
+                }
+            }
+            max_dd
+        }
+
+        // Moves to the bar an order on a signal from `signal_bar` fills on, resets its volume
+        // budget and fills as much of a carried-over remainder as that budget allows. A buy or
+        // sell slice the instrument would refuse waits for a later bar, unless the whole
+        // remainder would be refused too; then the order is cancelled.
+        pub fn begin_bar(&mut self, signal_bar: usize) -> Result<(), Box<dyn std::error::Error>> {
+            let bar = self.fill_bar(signal_bar);
+            self.bar = if bar < self.exec().Date.len() { Some(bar) } else { None };
+            self.bar_filled = 0.0;
+
+            let index = match self.pending {
+                Some(index) => index,
+                None => return Ok(()),
+            };
+            let (date, price) = match self.fill(signal_bar) {
+                Some(fill) => fill,
+                None => return Ok(()),
+            };
+            let side = self.orders[index].side;
+            let mut remaining = self.orders[index].unfilled();
+            if side == Side::Close {
+                remaining = remaining.min(if self.is_long { self.long_quantity } else { self.short_quantity });
+                if !self.position || remaining <= 0.0 {
+                    self.pending = None;
+                    return Ok(());
+                }
+            }
+            let quantity = remaining.min(self.capacity());
+            if quantity <= 0.0 {
+                return Ok(());
+            }
+            let quantity = match side {
+                Side::Buy | Side::Sell => match self.instrument.validate_order(price, quantity) {
+                    Ok(quantity) => quantity,
+                    Err(reason) => {
+                        if self.instrument.validate_order(price, remaining).is_err() {
+                            self.reject(&date, side, price, remaining, reason);
+                            self.log(format!("CANCEL {}, {}", date, remaining));
+                            self.pending = None;
+                        }
+                        return Ok(());
+                    }
+                },
+                Side::Close | Side::Liquidate => quantity,
+            };
+            self.bar_filled += quantity;
+            self.orders[index].filled += quantity;
+            if self.orders[index].unfilled() <= 0.0 || (side == Side::Close && quantity >= remaining) {
+                self.pending = None;
+            }
+            match side {
+                Side::Buy => self.fill_buy(date, price, quantity),
+                Side::Sell => self.fill_sell(date, price, quantity),
+                Side::Close | Side::Liquidate => self.fill_close(&date, &price, quantity),
+            }
+        }
+
+        // Volume still available on the current bar under the participation limit
+        fn capacity(&self) -> f64 {
+            match self.bar {
+                Some(bar) if self.participation > 0.0 => {
+                    let room = (self.participation * self.exec().Volume[bar] as f64 - self.bar_filled).max(0.0);
+                    self.instrument.round_quantity(room)
+                }
+                _ => f64::INFINITY,
+            }
+        }
+
+        // Records a new order, cancelling any remainder still working, and returns the
+        // quantity that fills now; the rest is carried to later bars
+        fn submit(&mut self, date: &str, side: Side, quantity: f64) -> f64 {
+            if let Some(index) = self.pending.take() {
+                let unfilled = self.orders[index].unfilled();
+                self.log(format!("CANCEL {}, {}", date, unfilled));
+            }
+            let filled = quantity.min(self.capacity());
+            self.bar_filled += filled;
+            self.orders.push(Order {
+                date: date.to_string(),
+                side,
+                requested: quantity,
+                filled,
+            });
+            if filled < quantity {
+                self.pending = Some(self.orders.len() - 1);
+                self.log(format!("PARTIAL {:?} {}, {} of {}", side, date, filled, quantity));
+            }
+            filled
         }
 
         pub fn buy(&mut self, date: String, price: f64, quantity: f64) -> Result<(), Box<dyn std::error::Error>> {
             if quantity <= 0.0 || price <= 0.0 {
                 return Err("Invalid quantity or price".into());
             }
-            let adjusted_price = price * (1.0 + self.slippage);
-            let comm = self.calculate_comm(adjusted_price, quantity)?;
-            let slip = self.calculate_slippage(adjusted_price, quantity);
-            
-            if !self.position {
-                self.is_long = true;
-                self.position = true;
-                self.long_quantity = quantity;
-                self.long_entry_price = adjusted_price;
-                self.balance -= adjusted_price * quantity + comm + slip;
-            } else if self.is_long {
-                let total_cost = self.long_entry_price * self.long_quantity + adjusted_price * quantity;
-                self.long_quantity += quantity;
-                self.long_entry_price = total_cost / self.long_quantity;
-                self.balance -= adjusted_price * quantity + comm + slip;
-            } else if self.is_short {
-                let short_pnl = (self.short_entry_price - adjusted_price) * self.short_quantity;
-                self.balance += short_pnl - comm - slip;
-            }
-
-            self.log(format!("BUY {}, {}, {}", date, adjusted_price, quantity));
+            if self.instrument.expired(&date) {
+                self.reject(&date, Side::Buy, price, quantity, "instrument has expired".to_string());
+                return Ok(());
+            }
+            let quantity = self.margin_limit(&date, price, quantity, true);
+            if quantity <= 0.0 {
+                return Ok(());
+            }
+            let quantity = match self.instrument.validate_order(price, quantity) {
+                Ok(quantity) => quantity,
+                Err(reason) => {
+                    self.reject(&date, Side::Buy, price, quantity, reason);
+                    return Ok(());
+                }
+            };
+            let filled = self.submit(&date, Side::Buy, quantity);
+            if filled > 0.0 {
+                self.fill_buy(date, price, filled)?;
+            }
             Ok(())
         }
 
@@ -144,87 +722,479 @@ pub mod backtest {
             if quantity <= 0.0 || price <= 0.0 {
                 return Err("Invalid quantity or price".into());
             }
-            let comm = self.calculate_comm(price, quantity)?;
-            let _slip = self.calculate_slippage(price, quantity);
-            let adjusted_price = price * (1.0 - self.slippage);
+            if self.instrument.expired(&date) {
+                self.reject(&date, Side::Sell, price, quantity, "instrument has expired".to_string());
+                return Ok(());
+            }
+            let quantity = self.margin_limit(&date, price, quantity, false);
+            let quantity = self.locate_limit(&date, quantity);
+            if quantity <= 0.0 {
+                return Ok(());
+            }
+            let quantity = match self.instrument.validate_order(price, quantity) {
+                Ok(quantity) => quantity,
+                Err(reason) => {
+                    self.reject(&date, Side::Sell, price, quantity, reason);
+                    return Ok(());
+                }
+            };
+            let filled = self.submit(&date, Side::Sell, quantity);
+            if filled > 0.0 {
+                self.fill_sell(date, price, filled)?;
+            }
+            Ok(())
+        }
 
+        // Closes are rounded to the quantity step but exempt from the minimums, so a position
+        // can always be exited
+        pub fn close(&mut self, date: &String, price: &f64, quantity: f64) -> Result<(), Box<dyn std::error::Error>> {
+            if quantity <= 0.0 || *price <= 0.0 {
+                return Err("Invalid quantity or price".into());
+            }
+            let quantity = self.instrument.round_quantity(quantity);
+            if quantity <= 0.0 {
+                self.reject(date, Side::Close, *price, quantity, "quantity is less than one step".to_string());
+                return Ok(());
+            }
+            let filled = self.submit(date, Side::Close, quantity);
+            if filled > 0.0 {
+                self.fill_close(date, price, filled)?;
+            }
+            Ok(())
+        }
+
+        fn reject(&mut self, date: &str, side: Side, price: f64, quantity: f64, reason: String) {
+            self.log(format!("REJECT {:?} {}, {} at {}: {}", side, date, quantity, price, reason));
+            self.rejections.push(Rejection {
+                date: date.to_string(),
+                side,
+                price,
+                quantity,
+                reason,
+            });
+        }
+
+        // Largest part of a buy (or sell) at `price` the account can carry: the position after
+        // it may not need more than equity as initial margin or exceed the leverage limit.
+        // Orders that only reduce exposure are never cut.
+        fn margin_limit(&mut self, date: &str, price: f64, quantity: f64, buy: bool) -> f64 {
+            let leverage = if self.max_leverage > 0.0 { 1.0 / self.max_leverage } else { 0.0 };
+            let factor = self.initial_margin.max(leverage);
+            if factor <= 0.0 {
+                return quantity;
+            }
+            let net = self.long_quantity - self.short_quantity;
+            let max_net = (self.equity_value(price) / self.instrument.notional(price, factor)).max(0.0);
+            let room = if buy { max_net - net } else { max_net + net };
+            let allowed = quantity.min(room.max(0.0));
+            if allowed < quantity {
+                let side = if buy { "BUY" } else { "SELL" };
+                self.log(format!("MARGIN {} {}, {} cut to {}", side, date, quantity, allowed));
+            }
+            allowed
+        }
+
+        // Cuts the part of a sell that would short more than the locate table allows for the day
+        fn locate_limit(&mut self, date: &str, quantity: f64) -> f64 {
+            if self.short_costs.locates.is_none() {
+                return quantity;
+            }
+            let long = if self.position && self.is_long { self.long_quantity } else { 0.0 };
+            let short = if self.position && self.is_short { self.short_quantity } else { 0.0 };
+            let new_short = (quantity - long).max(0.0);
+            let borrowable = (self.short_costs.available(date.get(..10).unwrap_or(date)) - short).max(0.0);
+            if new_short <= borrowable {
+                return quantity;
+            }
+            self.log(format!("LOCATE SELL {}, short of {} cut to {}", date, new_short, borrowable));
+            quantity - new_short + borrowable
+        }
+
+        // Bookkeeping once a bar's orders are done: overnight carry costs, the margin check
+        // and the equity mark at the close. A position rolled on the bar is marked at the
+        // new contract's price, which an unadjusted feed only shows from the next bar.
+        pub fn end_bar(&mut self, bar: usize) -> Result<(), Box<dyn std::error::Error>> {
+            self.carry_costs(bar);
+            self.apply_funding(bar)?;
+            let rolled = self.roll_position(bar)?;
+            self.expire(bar)?;
+            self.check_margin(bar)?;
+            let close = rolled.unwrap_or(self.exec().Close[bar]);
+            self.mark(close);
+            Ok(())
+        }
+
+        // On the first bar of a new day, charges borrow fees for every calendar day since the
+        // last bar on the short held at its close, and settles dividends going ex on the new
+        // day with whoever held the shares overnight: longs receive them, shorts pay them
+        fn carry_costs(&mut self, bar: usize) {
+            let feed = self.exec();
+            let date = feed.Date[bar].clone();
+            let close = feed.Close[bar];
+            let today = date.get(..10).unwrap_or(&date).to_string();
+            let day = match day_number(&today) {
+                Some(day) => day,
+                None => return,
+            };
+
+            if let Some((last, last_day, long, short, price)) = self.carry.take() {
+                if day > last {
+                    let fee = self.instrument.notional(price, short) * self.short_costs.rate(&last_day) * (day - last) as f64
+                        / self.short_costs.day_count;
+                    if fee > 0.0 {
+                        self.balance -= fee;
+                        self.total_borrow += fee;
+                        self.log(format!("BORROW {}, {}", date, fee));
+                    }
+                    // per share, so per contract unit like any price
+                    let dividend = self.short_costs.dividend(&today);
+                    if dividend != 0.0 && (long > 0.0 || short > 0.0) {
+                        let (received, paid) = (self.instrument.notional(dividend, long), self.instrument.notional(dividend, short));
+                        self.balance += received - paid;
+                        self.dividends_received += received;
+                        self.dividends_paid += paid;
+                        self.log(format!("DIVIDEND {}, {}", date, received - paid));
+                    }
+                }
+            }
+            let long = if self.position && self.is_long { self.long_quantity } else { 0.0 };
+            let short = if self.position && self.is_short { self.short_quantity } else { 0.0 };
+            self.carry = Some((day, today, long, short, close));
+        }
+
+        // Settles the funding events since the last bar on the position held over them, valued
+        // at the last close: longs pay and shorts receive a positive rate
+        fn apply_funding(&mut self, bar: usize) -> Result<(), Box<dyn std::error::Error>> {
+            if self.funding.is_empty() {
+                return Ok(());
+            }
+            let feed = self.exec();
+            let date = feed.Date[bar].clone();
+            let close = feed.Close[bar];
+
+            if let Some((last, net, price)) = self.funding_carry.take() {
+                if net != 0.0 {
+                    for (time, rate) in self.funding.between(&last, &date)? {
+                        let payment = self.instrument.notional(price, net) * rate;
+                        self.balance -= payment;
+                        if payment > 0.0 {
+                            self.funding_paid += payment;
+                        } else {
+                            self.funding_received -= payment;
+                        }
+                        self.log(format!("FUNDING {}, {}", time, -payment));
+                    }
+                }
+            }
+            let long = if self.position && self.is_long { self.long_quantity } else { 0.0 };
+            let short = if self.position && self.is_short { self.short_quantity } else { 0.0 };
+            self.funding_carry = Some((date, long - short, close));
+            Ok(())
+        }
+
+        // Moves an open position into the next contract when `bar` is a roll: it is sold at the
+        // old price and bought back at the new one, paying commission and slippage on both legs
+        // plus the roll cost. The cash difference only matters on an unadjusted feed. Returns
+        // the new price when a position was rolled.
+        fn roll_position(&mut self, bar: usize) -> Result<Option<f64>, Box<dyn std::error::Error>> {
+            let roll = match self.rolls.iter().find(|r| r.bar == bar) {
+                Some(roll) => roll.clone(),
+                None => return Ok(None),
+            };
             if !self.position {
-                self.is_short = true;
-                self.position = true;
-                self.short_quantity = quantity;
-                self.balance += adjusted_price * quantity - comm;
-            } else if self.is_short {
-                self.short_quantity += quantity;
-                self.balance += adjusted_price * quantity - comm;
-            } else if self.is_long && quantity > self.long_quantity {
-                self.is_long = false;
-                self.is_short = true;
-                self.short_quantity = quantity - self.long_quantity;
-                self.long_quantity = 0.0;
-                self.balance += adjusted_price * quantity - comm;
-            } else if self.is_long && quantity <= self.long_quantity {
-                self.long_quantity -= quantity;
-                self.balance += adjusted_price * quantity - comm;
+                return Ok(None);
+            }
+            let is_long = self.is_long;
+            let quantity = if is_long { self.long_quantity } else { self.short_quantity };
+            let slip_out = self.calculate_slippage(roll.old_price, quantity);
+            let slip_in = self.calculate_slippage(roll.new_price, quantity);
+            let (exit, entry) = if is_long {
+                (roll.old_price - slip_out, roll.new_price + slip_in)
+            } else {
+                (roll.old_price + slip_out, roll.new_price - slip_in)
+            };
+            let comm = self.calculate_comm(exit, quantity)? + self.calculate_comm(entry, quantity)?;
+            let fee = self.roll_cost * quantity;
+            let net = if is_long { quantity } else { -quantity };
+            self.balance += self.instrument.notional(exit - entry, net) - comm - fee;
+            if is_long {
+                self.long_entry_price = entry;
+            } else {
+                self.short_entry_price = entry;
+            }
+            self.total_roll_cost += comm + fee + self.instrument.notional(slip_out + slip_in, quantity);
+            self.rolls_done += 1;
+            self.log(format!("ROLL {}, {} -> {}, {}", roll.date, roll.from, roll.to, quantity));
+            Ok(Some(roll.new_price))
+        }
+
+        // Closes a position still open at the close of the instrument's expiry day
+        fn expire(&mut self, bar: usize) -> Result<(), Box<dyn std::error::Error>> {
+            let feed = self.exec();
+            let (date, close) = (feed.Date[bar].clone(), feed.Close[bar]);
+            if !self.position || !self.instrument.expiring(&date) {
+                return Ok(());
+            }
+            let quantity = if self.is_long { self.long_quantity } else { self.short_quantity };
+            self.log(format!("EXPIRY {}, {}", date, quantity));
+            self.fill_close(&date, &close, quantity)
+        }
+
+        // End-of-bar margin check for `bar`. The position is liquidated in full at the price
+        // where equity falls to the maintenance margin, or at the open when the bar gaps
+        // through it. Otherwise a margin call is counted when equity at the close no longer
+        // covers the initial margin. Returns whether a liquidation happened.
+        pub fn check_margin(&mut self, bar: usize) -> Result<bool, Box<dyn std::error::Error>> {
+            if !self.position || (self.maintenance_margin <= 0.0 && self.initial_margin <= 0.0) {
+                self.in_margin_call = false;
+                return Ok(false);
+            }
+            let feed = self.exec();
+            let (date, mut open, mut high, mut low, close) = (feed.Date[bar].clone(), feed.Open[bar], feed.High[bar], feed.Low[bar], feed.Close[bar]);
+            // after a fill later than the open the position only saw the bar from the fill
+            // price to the close; what traded before it cannot liquidate the position
+            if self.bar == Some(bar) && self.bar_filled > 0.0 && self.fill_model != FillModel::NextOpen {
+                let fill = self.fill_price(bar);
+                (open, high, low) = (fill, fill.max(close), fill.min(close));
+            }
+            let is_long = self.is_long;
+            let quantity = if is_long { self.long_quantity } else { self.short_quantity };
+            let m = self.maintenance_margin;
+            let contracts = self.instrument.notional(1.0, quantity);
+
+            if m > 0.0 {
+                // equity(p) = m * quantity * multiplier * p
+                let (liquidation, hit) = if is_long {
+                    let p = -self.balance / (contracts * (1.0 - m));
+                    (p.min(open), p > 0.0 && low <= p)
+                } else {
+                    let p = self.balance / (contracts * (1.0 + m));
+                    (p.max(open), high >= p)
+                };
+                if hit {
+                    if let Some(index) = self.pending.take() {
+                        let unfilled = self.orders[index].unfilled();
+                        self.log(format!("CANCEL {}, {}", date, unfilled));
+                    }
+                    self.orders.push(Order {
+                        date: date.clone(),
+                        side: Side::Liquidate,
+                        requested: quantity,
+                        filled: quantity,
+                    });
+                    self.log(format!("LIQUIDATION {}, {}, {}", date, liquidation, quantity));
+                    self.fill_close(&date, &liquidation, quantity)?;
+                    if let Some(trade) = self.ledger.last_mut() {
+                        trade.liquidated = true;
+                    }
+                    self.in_margin_call = false;
+                    return Ok(true);
+                }
+            }
+
+            let exposure = self.instrument.notional(close, quantity);
+            let short_of_margin = self.equity_value(close) < self.initial_margin * exposure;
+            if short_of_margin && !self.in_margin_call {
+                self.margin_calls += 1;
+                self.log(format!("MARGIN CALL {}, {}", date, self.equity_value(close)));
+            }
+            self.in_margin_call = short_of_margin;
+            Ok(false)
+        }
+
+        // Position direction (long, short) for the trade ledger
+        fn side(&self) -> (bool, bool) {
+            (self.position && self.is_long, self.position && self.is_short)
+        }
+
+        // Closes the open round trip when an order flattened or reversed the position and
+        // opens a new one when it left the book long or short
+        fn record_trade(&mut self, date: &str, price: f64, before: (bool, bool), equity_before: f64) {
+            let after = self.side();
+            if after == before {
+                return;
+            }
+            let equity_after = self.equity_value(price);
+            if let Some(mut trade) = self.open_trade.take() {
+                trade.exit_date = date.to_string();
+                trade.exit_equity = equity_after;
+                if let Some(order) = self.orders.get(trade.order) {
+                    (trade.requested, trade.filled) = (order.requested, order.filled);
+                }
+                self.ledger.push(trade);
+            }
+            if after.0 || after.1 {
+                let flat = !before.0 && !before.1;
+                // the order being filled is always the last one placed
+                let order = self.orders.len().saturating_sub(1);
+                let (requested, filled) = self.orders.get(order).map_or((0.0, 0.0), |o| (o.requested, o.filled));
+                self.open_trade = Some(Trade {
+                    entry_date: date.to_string(),
+                    exit_date: String::new(),
+                    is_long: after.0,
+                    entry_equity: if flat { equity_before } else { equity_after },
+                    exit_equity: 0.0,
+                    liquidated: false,
+                    order,
+                    requested,
+                    filled,
+                });
+            }
+        }
+
+        // Opens or adds to a long, first covering any short
+        fn fill_buy(&mut self, date: String, price: f64, quantity: f64) -> Result<(), Box<dyn std::error::Error>> {
+            if quantity <= 0.0 || price <= 0.0 {
+                return Err("Invalid quantity or price".into());
+            }
+            let before = self.side();
+            let equity_before = self.equity_value(price);
+            let slip = self.calculate_slippage(price, quantity);
+            let fill_price = self.instrument.round_price(price + slip, true);
+            let comm = self.calculate_comm(fill_price, quantity)?;
+            self.balance -= self.instrument.notional(fill_price, quantity) + comm;
+
+            let mut rest = quantity;
+            if self.position && self.is_short {
+                let cover = rest.min(self.short_quantity);
+                self.short_quantity -= cover;
+                rest -= cover;
+                if self.short_quantity <= 0.0 {
+                    self.short_quantity = 0.0;
+                    self.is_short = false;
+                    self.position = false;
+                }
+            }
+            if rest > 0.0 {
+                if self.position && self.is_long {
+                    let total_cost = self.long_entry_price * self.long_quantity + fill_price * rest;
+                    self.long_quantity += rest;
+                    self.long_entry_price = total_cost / self.long_quantity;
+                } else {
+                    self.is_long = true;
+                    self.position = true;
+                    self.long_quantity = rest;
+                    self.long_entry_price = fill_price;
+                }
+            }
+
+            self.trades += 1;
+            self.record_trade(&date, price, before, equity_before);
+            self.log(format!("BUY {}, {}, {}", date, fill_price, quantity));
+            Ok(())
+        }
+
+        // Opens or adds to a short, first selling any long
+        fn fill_sell(&mut self, date: String, price: f64, quantity: f64) -> Result<(), Box<dyn std::error::Error>> {
+            if quantity <= 0.0 || price <= 0.0 {
+                return Err("Invalid quantity or price".into());
+            }
+            let before = self.side();
+            let equity_before = self.equity_value(price);
+            let slip = self.calculate_slippage(price, quantity);
+            let fill_price = self.instrument.round_price(price - slip, false);
+            let comm = self.calculate_comm(fill_price, quantity)?;
+            self.balance += self.instrument.notional(fill_price, quantity) - comm;
+
+            let mut rest = quantity;
+            if self.position && self.is_long {
+                let sold = rest.min(self.long_quantity);
+                self.long_quantity -= sold;
+                rest -= sold;
                 if self.long_quantity <= 0.0 {
+                    self.long_quantity = 0.0;
                     self.is_long = false;
                     self.position = false;
                 }
             }
-            self.log(format!("SELL {}, {}, {}", date, adjusted_price, quantity));
+            if rest > 0.0 {
+                if self.position && self.is_short {
+                    let total = self.short_entry_price * self.short_quantity + fill_price * rest;
+                    self.short_quantity += rest;
+                    self.short_entry_price = total / self.short_quantity;
+                } else {
+                    self.is_short = true;
+                    self.position = true;
+                    self.short_quantity = rest;
+                    self.short_entry_price = fill_price;
+                }
+            }
+
+            self.trades += 1;
+            self.record_trade(&date, price, before, equity_before);
+            self.log(format!("SELL {}, {}, {}", date, fill_price, quantity));
             Ok(())
         }
 
-        pub fn close(&mut self, date: &String, price: &f64, quantity: f64) -> Result<(), Box<dyn std::error::Error>> {
+        // Reduces the open position by up to `quantity`, never reversing it
+        fn fill_close(&mut self, date: &String, price: &f64, quantity: f64) -> Result<(), Box<dyn std::error::Error>> {
             if quantity <= 0.0 || *price <= 0.0 {
                 return Err("Invalid quantity or price".into());
             }
-            let comm = self.calculate_comm(*price, quantity)?;
-            let _slip = self.calculate_slippage(*price, quantity);
-            let adjusted_price = if self.is_long {
-                price * (1.0 - self.slippage)
+            if !self.position {
+                return Ok(());
+            }
+            let before = self.side();
+            let equity_before = self.equity_value(*price);
+            let is_long = self.is_long;
+            let close_quantity = quantity.min(if is_long { self.long_quantity } else { self.short_quantity });
+            let slip = self.calculate_slippage(*price, close_quantity);
+            let fill_price = if is_long {
+                self.instrument.round_price(price - slip, false)
             } else {
-                price * (1.0 + self.slippage)
+                self.instrument.round_price(price + slip, true)
             };
+            let comm = self.calculate_comm(fill_price, close_quantity)?;
 
-            if self.is_long {
-                let close_quantity = quantity.min(self.long_quantity);
+            if is_long {
                 self.long_quantity -= close_quantity;
-                self.balance += adjusted_price * close_quantity - comm;
+                self.balance += self.instrument.notional(fill_price, close_quantity) - comm;
                 if self.long_quantity <= 0.0 {
                     self.is_long = false;
                     self.position = false;
                     self.long_quantity = 0.0;
                 }
-            } else if self.is_short {
-                let close_quantity = quantity.min(self.short_quantity);
+            } else {
                 self.short_quantity -= close_quantity;
-                self.balance -= adjusted_price * close_quantity - comm;
+                self.balance -= self.instrument.notional(fill_price, close_quantity) + comm;
                 if self.short_quantity <= 0.0 {
                     self.is_short = false;
                     self.position = false;
                     self.short_quantity = 0.0;
                 }
             }
-            self.log(format!("CLOSE {}, {}, {}", date, adjusted_price, quantity));
+            self.trades += 1;
+            self.record_trade(date, *price, before, equity_before);
+            self.log(format!("CLOSE {}, {}, {}", date, fill_price, close_quantity));
             Ok(())
         }
 
         pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-            println!("running backtest...");
-            println!("starting balance: {}", self.balance);
-
+            if self.verbose {
+                println!("running backtest...");
+                println!("starting balance: {}", self.balance);
+            }
 
+            // the book is flattened in full at the last close, whatever the participation limit
+            self.bar = None;
+            let exec = self.execution_ohlcv();
             if self.is_long && self.long_quantity > 0.0 {
-                self.close(self.ohlcv.get_ohlcv().Date.last().ok_or("No data available")?,
-                          self.ohlcv.get_ohlcv().Close.last().ok_or("No data available")?,
+                self.close(exec.Date.last().ok_or("No data available")?,
+                          exec.Close.last().ok_or("No data available")?,
                           self.long_quantity)?;
             } else if self.is_short && self.short_quantity > 0.0 {
-                self.close(self.ohlcv.get_ohlcv().Date.last().ok_or("No data available")?,
-                          self.ohlcv.get_ohlcv().Close.last().ok_or("No data available")?,
+                self.close(exec.Date.last().ok_or("No data available")?,
+                          exec.Close.last().ok_or("No data available")?,
                           self.short_quantity)?;
             }
+            self.pending = None;
             self.final_balance = self.balance;
-            println!("final balance: {}", self.balance);
+            if let Some(last) = self.equity.last_mut() {
+                *last = self.balance;
+            }
+            if self.verbose {
+                println!("final balance: {}", self.balance);
+            }
             Ok(())
         }
 
@@ -236,7 +1206,384 @@ pub mod backtest {
             println!("Return: {}", (self.final_balance - self.init_balance) / self.init_balance * 100.0);
             println!("Commission: {}", self.total_commission);
             println!("Slippage: {}", self.total_slippage);
+            println!("Trades: {}", self.trades);
+            if !self.ledger.is_empty() {
+                let wins = self.ledger.iter().filter(|t| t.pnl() > 0.0).count();
+                println!("Round Trips: {}", self.ledger.len());
+                println!("Win Rate: {}", wins as f64 / self.ledger.len() as f64 * 100.0);
+            }
+            if self.initial_margin > 0.0 || self.maintenance_margin > 0.0 {
+                println!("Margin Calls: {}", self.margin_calls);
+                println!("Liquidations: {}", self.ledger.iter().filter(|t| t.liquidated).count());
+            }
+            if self.total_borrow > 0.0 {
+                println!("Borrow Fees: {}", self.total_borrow);
+            }
+            if self.dividends_paid > 0.0 || self.dividends_received > 0.0 {
+                println!("Dividends Paid: {}", self.dividends_paid);
+                println!("Dividends Received: {}", self.dividends_received);
+            }
+            if !self.funding.is_empty() {
+                let net = self.funding_received - self.funding_paid;
+                println!("Funding Paid: {}", self.funding_paid);
+                println!("Funding Received: {}", self.funding_received);
+                println!("Net Funding: {}", net);
+                println!("Trading PnL: {}", self.final_balance - self.init_balance - net);
+            }
+            if !self.rejections.is_empty() {
+                println!("Rejected Orders: {}", self.rejections.len());
+            }
+            if self.rolls_done > 0 {
+                println!("Rolls: {}", self.rolls_done);
+                println!("Roll Costs: {}", self.total_roll_cost);
+            }
+            if self.participation > 0.0 && !self.orders.is_empty() {
+                let requested: f64 = self.orders.iter().map(|o| o.requested).sum();
+                let filled: f64 = self.orders.iter().map(|o| o.filled).sum();
+                let partial = self.orders.iter().filter(|o| o.unfilled() > 0.0).count();
+                println!("Orders: {} ({} not filled in full)", self.orders.len(), partial);
+                println!("Unfilled Quantity: {}", requested - filled);
+                println!("Fill Ratio: {}", filled / requested * 100.0);
+            }
+            println!("Max Drawdown: {}", self.max_drawdown() * 100.0);
             Ok(())
         }
     }
-}
\ No newline at end of file
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::engiene::slippage::slippage::FixedTicks;
+
+        // one bar per price, trading at that price all bar
+        fn feed(prices: &[f64], volumes: &[u64]) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for (k, (&price, &volume)) in prices.iter().zip(volumes).enumerate() {
+                ohlcv.Date.push(format!("2024-01-{:02}", k + 1));
+                ohlcv.Open.push(price);
+                ohlcv.High.push(price);
+                ohlcv.Low.push(price);
+                ohlcv.Close.push(price);
+                ohlcv.Adj_Close.push(price);
+                ohlcv.Volume.push(volume);
+            }
+            ohlcv
+        }
+
+        // buys `quantity` on bar 1, then works the remainder through every later bar
+        fn work_buy(volumes: &[u64], quantity: f64) -> Backtest {
+            let mut backtest = Backtest::new(feed(&vec![10.0; volumes.len()], volumes));
+            backtest.verbose = false;
+            backtest.set_participation(0.1);
+            backtest.instrument.min_notional = 600.0;
+            backtest.begin_bar(0).unwrap();
+            backtest.buy("2024-01-02".to_string(), 10.0, quantity).unwrap();
+            backtest.end_bar(1).unwrap();
+            for signal_bar in 1..volumes.len() - 1 {
+                backtest.begin_bar(signal_bar).unwrap();
+                backtest.end_bar(signal_bar + 1).unwrap();
+            }
+            backtest
+        }
+
+        #[test]
+        fn remainder_below_the_minimum_notional_is_cancelled() {
+            // 100 a bar, leaving 50 (a notional of 500) for the third bar
+            let backtest = work_buy(&[1000, 1000, 1000, 1000, 1000], 250.0);
+            assert_eq!(backtest.long_quantity, 200.0);
+            assert_eq!(backtest.orders[0].filled, 200.0);
+            assert_eq!(backtest.rejections.len(), 1);
+            assert_eq!(backtest.rejections[0].quantity, 50.0);
+            assert_eq!(backtest.rejections[0].date, "2024-01-04");
+        }
+
+        #[test]
+        fn ledger_reports_the_opening_order_fill() {
+            let mut backtest = work_buy(&[1000, 1000, 1000, 1000, 1000], 250.0);
+            backtest.run().unwrap();
+            let trade = &backtest.ledger[0];
+            assert_eq!(trade.order, 0);
+            assert_eq!((trade.requested, trade.filled), (250.0, 200.0));
+            assert_eq!(trade.unfilled(), 50.0);
+            assert_eq!(trade.fill_ratio(), 0.8);
+        }
+
+        #[test]
+        fn slice_below_the_minimum_waits_for_volume() {
+            // bar 2 has room for 3 only; the remainder fills on bar 3
+            let backtest = work_buy(&[1000, 1000, 30, 1000, 1000], 200.0);
+            assert_eq!(backtest.long_quantity, 200.0);
+            assert!(backtest.rejections.is_empty());
+        }
+
+        #[test]
+        fn slippage_is_charged_once_per_fill() {
+            // two ticks of 0.25 on 2 contracts of 50: 50.0 a fill, through the fill price only
+            let mut backtest = Backtest::new(feed(&[100.0, 100.0, 100.0, 100.0], &[1000; 4]));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.set_comission(0.0);
+            backtest.instrument = Instrument::future("ES", 50.0, 0.25).unwrap();
+            backtest.set_slippage_model(Box::new(FixedTicks(2.0)));
+            backtest.begin_bar(0).unwrap();
+            backtest.buy("2024-01-02".to_string(), 100.0, 2.0).unwrap();
+            backtest.end_bar(1).unwrap();
+            assert_eq!(backtest.long_entry_price, 100.5);
+            assert_eq!(backtest.total_slippage, 50.0);
+            backtest.begin_bar(1).unwrap();
+            backtest.close(&"2024-01-03".to_string(), &100.0, 2.0).unwrap();
+            backtest.end_bar(2).unwrap();
+            assert_eq!(backtest.total_slippage, 100.0);
+            assert_eq!(backtest.balance, 100000.0 - 100.0);
+        }
+
+        // 10 bought or sold at 100 on the open of 2024-01-02, held to 2024-01-03
+        fn hold_over_funding(is_long: bool) -> Backtest {
+            let mut backtest = Backtest::new(feed(&[100.0, 100.0, 100.0], &[1000; 3]));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.set_comission(0.0);
+            backtest.instrument.multiplier = 2.0;
+            // the midnight event precedes the entry; the 08:00 one is on the bar after it
+            backtest.funding.insert("2024-01-02 00:00:00", 0.01).unwrap();
+            backtest.funding.insert("2024-01-02 08:00:00", 0.001).unwrap();
+            backtest.funding.insert("2024-01-04 00:00:00", 0.1).unwrap();
+            backtest.begin_bar(0).unwrap();
+            if is_long {
+                backtest.buy("2024-01-02".to_string(), 100.0, 10.0).unwrap();
+            } else {
+                backtest.sell("2024-01-02".to_string(), 100.0, 10.0).unwrap();
+            }
+            backtest.end_bar(1).unwrap();
+            backtest.begin_bar(1).unwrap();
+            backtest.end_bar(2).unwrap();
+            backtest
+        }
+
+        #[test]
+        fn positive_funding_is_paid_by_longs_and_received_by_shorts() {
+            // 0.001 of a notional of 10 * 100 * 2
+            let long = hold_over_funding(true);
+            assert!((long.funding_paid - 2.0).abs() < 1e-12);
+            assert_eq!(long.funding_received, 0.0);
+            assert!((long.balance - (100000.0 - 2000.0 - 2.0)).abs() < 1e-9);
+            let short = hold_over_funding(false);
+            assert!((short.funding_received - 2.0).abs() < 1e-12);
+            assert_eq!(short.funding_paid, 0.0);
+        }
+
+        // 10 of a 2x multiplier bought or sold at 100 on Friday 2024-01-05, held over the
+        // weekend, with 0.36 a year to borrow on a 360-day basis and 0.5 going ex on Monday
+        fn hold_over_weekend(is_long: bool) -> Backtest {
+            let mut ohlcv = feed(&[100.0, 100.0, 100.0], &[1000; 3]);
+            ohlcv.Date = vec!["2024-01-04".to_string(), "2024-01-05".to_string(), "2024-01-08".to_string()];
+            let mut backtest = Backtest::new(ohlcv);
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.set_comission(0.0);
+            backtest.instrument.multiplier = 2.0;
+            backtest.short_costs.borrow = BorrowRate::Fixed(0.36);
+            backtest.short_costs.dividends.insert("2024-01-08".to_string(), 0.5);
+            backtest.begin_bar(0).unwrap();
+            if is_long {
+                backtest.buy("2024-01-05".to_string(), 100.0, 10.0).unwrap();
+            } else {
+                backtest.sell("2024-01-05".to_string(), 100.0, 10.0).unwrap();
+            }
+            backtest.end_bar(1).unwrap();
+            backtest.begin_bar(1).unwrap();
+            backtest.end_bar(2).unwrap();
+            backtest
+        }
+
+        #[test]
+        fn borrow_accrues_over_every_calendar_day_and_shorts_pay_dividends() {
+            // three days of 0.001 on 10 * 100 * 2, and 0.5 on 10 * 2
+            let short = hold_over_weekend(false);
+            assert!((short.total_borrow - 6.0).abs() < 1e-9);
+            assert!((short.dividends_paid - 10.0).abs() < 1e-12);
+            assert!((short.balance - (100000.0 + 2000.0 - 6.0 - 10.0)).abs() < 1e-9);
+            let long = hold_over_weekend(true);
+            assert_eq!(long.total_borrow, 0.0);
+            assert!((long.dividends_received - 10.0).abs() < 1e-12);
+        }
+
+        #[test]
+        fn shorts_are_cut_to_the_locates() {
+            let mut backtest = Backtest::new(feed(&[100.0, 100.0, 100.0, 100.0], &[1000; 4]));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.short_costs.locates = Some(
+                [("2024-01-01".to_string(), 0.0), ("2024-01-03".to_string(), 5.0)].into_iter().collect(),
+            );
+            // nothing to borrow on the 2nd
+            backtest.begin_bar(0).unwrap();
+            backtest.sell("2024-01-02".to_string(), 100.0, 10.0).unwrap();
+            backtest.end_bar(1).unwrap();
+            assert!(!backtest.position);
+            // 5 on the 3rd, and only 5 over the whole short
+            backtest.begin_bar(1).unwrap();
+            backtest.sell("2024-01-03".to_string(), 100.0, 10.0).unwrap();
+            backtest.end_bar(2).unwrap();
+            assert_eq!(backtest.short_quantity, 5.0);
+            backtest.begin_bar(2).unwrap();
+            backtest.sell("2024-01-04".to_string(), 100.0, 10.0).unwrap();
+            backtest.end_bar(3).unwrap();
+            assert_eq!(backtest.short_quantity, 5.0);
+        }
+
+        #[test]
+        fn orders_trade_on_the_expiry_date_and_are_closed_out_at_its_close() {
+            let mut backtest = Backtest::new(feed(&[100.0, 101.0, 102.0, 103.0], &[1000; 4]));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.instrument.set_expiry("2024-01-02");
+            backtest.begin_bar(0).unwrap();
+            backtest.buy("2024-01-02".to_string(), 101.0, 10.0).unwrap();
+            backtest.end_bar(1).unwrap();
+            assert!(backtest.rejections.is_empty());
+            assert!(!backtest.position);
+            assert_eq!(backtest.ledger.len(), 1);
+            assert_eq!(backtest.ledger[0].exit_date, "2024-01-02");
+            // the day after, orders are rejected
+            backtest.begin_bar(1).unwrap();
+            backtest.sell("2024-01-03".to_string(), 102.0, 10.0).unwrap();
+            backtest.end_bar(2).unwrap();
+            assert!(!backtest.position);
+            assert_eq!(backtest.rejections.len(), 1);
+            assert_eq!(backtest.rejections[0].reason, "instrument has expired");
+        }
+
+        #[test]
+        fn unadjusted_roll_is_marked_at_the_new_contract() {
+            // the old contract closes at 102 on bar 2, where the position rolls into one at 110
+            let mut backtest = Backtest::new(feed(&[100.0, 101.0, 102.0, 111.0, 112.0], &[1000; 5]));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.set_rolls(
+                vec![Roll {
+                    bar: 2,
+                    date: "2024-01-03".to_string(),
+                    from: "F1".to_string(),
+                    to: "F2".to_string(),
+                    old_price: 102.0,
+                    new_price: 110.0,
+                }],
+                0.0,
+            );
+            backtest.begin_bar(0).unwrap();
+            backtest.buy("2024-01-02".to_string(), 101.0, 10.0).unwrap();
+            for bar in 1..5 {
+                if bar > 1 {
+                    backtest.begin_bar(bar - 1).unwrap();
+                }
+                backtest.end_bar(bar).unwrap();
+            }
+
+            assert_eq!(backtest.rolls_done, 1);
+            // ten contracts gain a point a bar in either contract, with no step at the roll
+            assert_eq!(backtest.equity, vec![100000.0, 100010.0, 100020.0, 100030.0]);
+        }
+
+        fn bars(ohlc: &[(f64, f64, f64, f64)]) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for (k, &(o, h, l, c)) in ohlc.iter().enumerate() {
+                ohlcv.Date.push(format!("2024-01-{:02}", k + 1));
+                ohlcv.Open.push(o);
+                ohlcv.High.push(h);
+                ohlcv.Low.push(l);
+                ohlcv.Close.push(c);
+                ohlcv.Adj_Close.push(c);
+                ohlcv.Volume.push(1000);
+            }
+            ohlcv
+        }
+
+        // buys `quantity` on a signal from bar 0 and holds it to the end under the margins
+        fn hold_long(ohlc: &[(f64, f64, f64, f64)], fill_model: FillModel, margins: (f64, f64), quantity: f64) -> Backtest {
+            let mut backtest = Backtest::new(bars(ohlc));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.set_fill_model(fill_model);
+            backtest.set_margin(margins.0, margins.1).unwrap();
+            backtest.begin_bar(0).unwrap();
+            let (date, price) = backtest.fill(0).unwrap();
+            backtest.buy(date, price, quantity).unwrap();
+            backtest.end_bar(backtest.fill_bar(0)).unwrap();
+            let mut signal_bar = 1;
+            while backtest.fill(signal_bar).is_some() {
+                backtest.begin_bar(signal_bar).unwrap();
+                backtest.end_bar(backtest.fill_bar(signal_bar)).unwrap();
+                signal_bar += 1;
+            }
+            backtest
+        }
+
+        const FLAT: (f64, f64, f64, f64) = (100.0, 100.0, 100.0, 100.0);
+
+        #[test]
+        fn liquidation_fills_at_the_maintenance_price() {
+            // 4000 at 100 on 100000: equity reaches 20% of notional at 93.75
+            let backtest = hold_long(&[FLAT, FLAT, (100.0, 100.0, 90.0, 95.0)], FillModel::NextOpen, (0.25, 0.2), 4000.0);
+            assert!(!backtest.position);
+            assert!(backtest.ledger[0].liquidated);
+            assert_eq!(backtest.orders.last().unwrap().side, Side::Liquidate);
+            assert!((backtest.balance - 75000.0).abs() < 1e-6);
+        }
+
+        #[test]
+        fn gap_through_the_maintenance_price_liquidates_at_the_open() {
+            let backtest = hold_long(&[FLAT, FLAT, (90.0, 92.0, 85.0, 91.0)], FillModel::NextOpen, (0.25, 0.2), 4000.0);
+            assert!(backtest.ledger[0].liquidated);
+            assert!((backtest.balance - 60000.0).abs() < 1e-6);
+        }
+
+        #[test]
+        fn margin_call_is_counted_once_per_shortfall() {
+            // 2000 at 100 needs half its notional as equity: short of it below 100
+            let closes = [100.0, 100.0, 90.0, 89.0, 100.0, 90.0];
+            let ohlc: Vec<_> = closes.iter().map(|&c| (c, c, c, c)).collect();
+            let backtest = hold_long(&ohlc, FillModel::NextOpen, (0.5, 0.1), 2000.0);
+            assert!(backtest.position);
+            assert_eq!(backtest.margin_calls, 2);
+        }
+
+        #[test]
+        fn orders_are_cut_to_the_margin_and_leverage_limits() {
+            let backtest = hold_long(&[FLAT, FLAT], FillModel::NextOpen, (0.5, 0.1), 5000.0);
+            assert_eq!(backtest.long_quantity, 2000.0);
+
+            let mut backtest = Backtest::new(bars(&[FLAT, FLAT]));
+            backtest.verbose = false;
+            backtest.set_balance(100000.0);
+            backtest.set_max_leverage(2.0);
+            backtest.begin_bar(0).unwrap();
+            backtest.buy("2024-01-02".to_string(), 100.0, 5000.0).unwrap();
+            assert_eq!(backtest.long_quantity, 2000.0);
+            // reducing exposure is never cut
+            backtest.sell("2024-01-02".to_string(), 100.0, 2000.0).unwrap();
+            assert!(!backtest.position);
+        }
+
+        #[test]
+        fn close_fill_is_not_liquidated_by_the_range_before_it() {
+            // the low of 50 traded before the entry at the close of the same bar
+            let backtest = hold_long(&[(100.0, 100.0, 50.0, 100.0), FLAT], FillModel::SignalClose, (0.25, 0.2), 4000.0);
+            assert!(backtest.position);
+            assert!(backtest.ledger.is_empty());
+            assert_eq!(backtest.long_quantity, 4000.0);
+        }
+
+        #[test]
+        fn average_price_fill_is_checked_from_the_fill_to_the_close() {
+            // filled at 92.5 with the liquidation price at 84.375; the low of 60 may have come first
+            let backtest = hold_long(&[FLAT, (100.0, 110.0, 60.0, 100.0)], FillModel::BarVwap, (0.25, 0.2), 4000.0);
+            assert!(backtest.position);
+
+            // filled at 82.5 and closing below the liquidation price of 71.875
+            let backtest = hold_long(&[FLAT, (100.0, 100.0, 60.0, 70.0)], FillModel::BarVwap, (0.25, 0.2), 4000.0);
+            assert!(backtest.ledger[0].liquidated);
+            assert!((backtest.balance - 57500.0).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+pub mod funding {
+    #![allow(dead_code)]
+    use crate::data_reader::data::data::timestamp;
+    use csv::Reader;
+    use std::collections::BTreeMap;
+
+    // Perpetual swap funding events: rate per event keyed by its time in seconds, with the
+    // time as written. Times and bar dates are compared as instants, so an event at
+    // "2024-01-02 00:00:00" falls on the bar dated "2024-01-02".
+    // A positive rate means longs pay shorts.
+    pub struct FundingRates {
+        pub rates: BTreeMap<i64, (String, f64)>,
+    }
+
+    fn parse_time(time: &str) -> Result<i64, Box<dyn std::error::Error>> {
+        timestamp(time).ok_or_else(|| format!("Funding time {} must be YYYY-MM-DD[ HH:MM[:SS]]", time).into())
+    }
+
+    impl FundingRates {
+        pub fn new() -> Self {
+            FundingRates { rates: BTreeMap::new() }
+        }
+
+        // Two-column CSV with a header (time, rate)
+        pub fn read_csv(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+            let mut reader = Reader::from_path(filename)?;
+            let mut funding = FundingRates::new();
+            for line in reader.records() {
+                let line = line?;
+                let time = line.get(0).ok_or("Missing funding time")?;
+                let rate: f64 = line.get(1).ok_or("Missing funding rate")?.parse()?;
+                funding.insert(time, rate)?;
+            }
+            Ok(funding)
+        }
+
+        pub fn insert(&mut self, time: &str, rate: f64) -> Result<(), Box<dyn std::error::Error>> {
+            self.rates.insert(parse_time(time)?, (time.to_string(), rate));
+            Ok(())
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.rates.is_empty()
+        }
+
+        // Events after `from` up to and including `to`
+        pub fn between(&self, from: &str, to: &str) -> Result<Vec<(String, f64)>, Box<dyn std::error::Error>> {
+            let (from, to) = (parse_time(from)?, parse_time(to)?);
+            if from >= to {
+                return Ok(Vec::new());
+            }
+            Ok(self
+                .rates
+                .range((std::ops::Bound::Excluded(from), std::ops::Bound::Included(to)))
+                .map(|(_, (t, r))| (t.clone(), *r))
+                .collect())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn rates(events: &[(&str, f64)]) -> FundingRates {
+            let mut funding = FundingRates::new();
+            for (time, rate) in events {
+                funding.insert(time, *rate).unwrap();
+            }
+            funding
+        }
+
+        #[test]
+        fn times_are_compared_as_instants() {
+            assert_eq!(timestamp("2024-01-02"), timestamp("2024-01-02 00:00:00"));
+            assert_eq!(timestamp("2024-01-02T08:00"), Some(timestamp("2024-01-02").unwrap() + 8 * 3600));
+            assert_eq!(timestamp("2024-01-02 08:00:00Z"), timestamp("2024-01-02 08:00"));
+            assert!(timestamp("2024-01-02 25:00").is_none());
+            assert!(FundingRates::new().insert("yesterday", 0.1).is_err());
+        }
+
+        #[test]
+        fn events_fall_after_the_last_bar_up_to_and_including_this_one() {
+            let funding = rates(&[
+                ("2024-01-02 00:00:00", 0.1),
+                ("2024-01-02 08:00:00", 0.2),
+                ("2024-01-02 16:00:00", 0.3),
+                ("2024-01-03 00:00:00", 0.4),
+            ]);
+            let times = |from, to| -> Vec<f64> { funding.between(from, to).unwrap().iter().map(|e| e.1).collect() };
+            // midnight belongs to the bar dated that day, not the next one
+            assert_eq!(times("2024-01-01", "2024-01-02"), vec![0.1]);
+            assert_eq!(times("2024-01-02", "2024-01-03"), vec![0.2, 0.3, 0.4]);
+            // intraday bars
+            assert_eq!(times("2024-01-02 08:00", "2024-01-02 16:00"), vec![0.3]);
+            assert!(times("2024-01-03", "2024-01-03").is_empty());
+            assert!(funding.between("2024-01-02", "tomorrow").is_err());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+pub mod instrument {
+    #![allow(dead_code)]
+
+    // Contract terms and trading rules of what a Backtest trades. Spot instruments have a unit
+    // multiplier and no tick, lot, minimums or expiry; a 0.0 tick or lot size means any price
+    // or quantity.
+    #[derive(Debug, Clone)]
+    pub struct Instrument {
+        pub symbol: String,
+        // currency value of one point of price per contract
+        pub multiplier: f64,
+        pub tick_size: f64,
+        // quantity step; order quantities are rounded down to a multiple of it
+        pub lot_size: f64,
+        pub min_quantity: f64,
+        // smallest price * quantity * multiplier of an order
+        pub min_notional: f64,
+        // whether quantities below one unit trade; only whole units otherwise
+        pub fractional: bool,
+        // fractions of contract notional, applied to the Backtest margins when non-zero
+        pub initial_margin: f64,
+        pub maintenance_margin: f64,
+        // last trading day ("YYYY-MM-DD"); positions still open are closed on it
+        pub expiry: Option<String>,
+    }
+
+    impl Instrument {
+        pub fn spot(symbol: &str) -> Self {
+            Instrument {
+                symbol: symbol.to_string(),
+                multiplier: 1.0,
+                tick_size: 0.0,
+                lot_size: 0.0,
+                min_quantity: 0.0,
+                min_notional: 0.0,
+                fractional: true,
+                initial_margin: 0.0,
+                maintenance_margin: 0.0,
+                expiry: None,
+            }
+        }
+
+        // Whole contracts of `multiplier` units with prices on a `tick_size` grid
+        pub fn future(symbol: &str, multiplier: f64, tick_size: f64) -> Result<Self, Box<dyn std::error::Error>> {
+            if multiplier <= 0.0 || tick_size < 0.0 {
+                return Err("Multiplier must be positive and tick size non-negative".into());
+            }
+            Ok(Instrument {
+                symbol: symbol.to_string(),
+                multiplier,
+                tick_size,
+                lot_size: 1.0,
+                min_quantity: 0.0,
+                min_notional: 0.0,
+                fractional: false,
+                initial_margin: 0.0,
+                maintenance_margin: 0.0,
+                expiry: None,
+            })
+        }
+
+        pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+            if self.multiplier <= 0.0 {
+                return Err("Multiplier must be positive".into());
+            }
+            if self.tick_size < 0.0 || self.lot_size < 0.0 || self.min_quantity < 0.0 || self.min_notional < 0.0 {
+                return Err("Tick size, lot size and minimums must be non-negative".into());
+            }
+            if !self.fractional && self.lot_size.fract() != 0.0 {
+                return Err("Lot size must be whole when fractional quantities are not allowed".into());
+            }
+            Ok(())
+        }
+
+        pub fn set_margin(&mut self, initial: f64, maintenance: f64) {
+            self.initial_margin = initial;
+            self.maintenance_margin = maintenance;
+        }
+
+        pub fn set_expiry(&mut self, expiry: &str) {
+            self.expiry = Some(expiry.to_string());
+        }
+
+        // Price value of `quantity` contracts
+        pub fn notional(&self, price: f64, quantity: f64) -> f64 {
+            price * quantity * self.multiplier
+        }
+
+        // Onto the tick grid, up for prices paid and down for prices received
+        pub fn round_price(&self, price: f64, up: bool) -> f64 {
+            if self.tick_size <= 0.0 {
+                return price;
+            }
+            let ticks = price / self.tick_size;
+            let ticks = if up { (ticks - 1e-9).ceil() } else { (ticks + 1e-9).floor() };
+            ticks * self.tick_size
+        }
+
+        // Smallest quantity increment; 0.0 when any quantity trades
+        pub fn step(&self) -> f64 {
+            if self.lot_size > 0.0 {
+                self.lot_size
+            } else if !self.fractional {
+                1.0
+            } else {
+                0.0
+            }
+        }
+
+        // Down to whole steps
+        pub fn round_quantity(&self, quantity: f64) -> f64 {
+            let step = self.step();
+            if step <= 0.0 || !quantity.is_finite() {
+                return quantity;
+            }
+            ((quantity / step) + 1e-9).floor() * step
+        }
+
+        // Quantity an order for `quantity` at `price` goes in for once rounded, or why the
+        // exchange would refuse it
+        pub fn validate_order(&self, price: f64, quantity: f64) -> Result<f64, String> {
+            let rounded = self.round_quantity(quantity);
+            if rounded <= 0.0 {
+                return Err(format!("quantity {} is less than one step of {}", quantity, self.step()));
+            }
+            if rounded < self.min_quantity {
+                return Err(format!("quantity {} is below the minimum of {}", rounded, self.min_quantity));
+            }
+            let notional = self.notional(price, rounded);
+            if notional < self.min_notional {
+                return Err(format!("notional {} is below the minimum of {}", notional, self.min_notional));
+            }
+            Ok(rounded)
+        }
+
+        // Whether `date` is after the expiry; the expiry date itself still trades
+        pub fn expired(&self, date: &str) -> bool {
+            match &self.expiry {
+                Some(expiry) => date.get(..10).unwrap_or(date) > expiry.as_str(),
+                None => false,
+            }
+        }
+
+        // Whether a position held at the end of `date` must be closed out: on or after the expiry
+        pub fn expiring(&self, date: &str) -> bool {
+            match &self.expiry {
+                Some(expiry) => date.get(..10).unwrap_or(date) >= expiry.as_str(),
+                None => false,
+            }
+        }
+    }
+}
@@ -1 +1,7 @@
-pub mod engiene;
\ No newline at end of file
+pub mod engiene;
+pub mod portfolio;
+pub mod slippage;
+pub mod commission;
+pub mod shorting;
+pub mod funding;
+pub mod instrument;
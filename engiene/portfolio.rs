@@ -0,0 +1,102 @@
+pub mod portfolio {
+    #![allow(dead_code)]
+    use crate::data_reader::data::data::DataFeed;
+    use crate::engiene::engiene::backtest::{Backtest, PramVal, Prams};
+    use crate::engiene::commission::commission::CommissionModel;
+
+    // Several instruments traded together. Each leg keeps its own Backtest book on the
+    // shared calendar; the starting balance is split evenly across the legs.
+    pub struct Portfolio {
+        pub names: Vec<String>,
+        pub legs: Vec<Backtest>,
+    }
+
+    impl Portfolio {
+        pub fn new() -> Self {
+            Portfolio {
+                names: Vec::new(),
+                legs: Vec::new(),
+            }
+        }
+
+        pub fn add_leg(&mut self, name: &str, data: DataFeed) -> Result<usize, Box<dyn std::error::Error>> {
+            if let Some(first) = self.legs.first() {
+                if first.ohlcv.Date != data.Date {
+                    return Err(format!("Leg {} is not aligned with {}", name, self.names[0]).into());
+                }
+            }
+            self.names.push(name.to_string());
+            self.legs.push(Backtest::new(data));
+            Ok(self.legs.len() - 1)
+        }
+
+        // Keys prefixed with a leg name and a dot ("BTC.commission_model") apply to that
+        // leg only and override the shared key
+        pub fn set_params(&mut self, prams: Prams) -> Result<(), Box<dyn std::error::Error>> {
+            if self.legs.is_empty() {
+                return Err("Portfolio has no legs".into());
+            }
+            let n = self.legs.len() as f64;
+            for (name, leg) in self.names.iter().zip(self.legs.iter_mut()) {
+                let mut leg_prams = Prams {
+                    data: prams.data.iter().filter(|(k, _)| !k.contains('.')).map(|(k, v)| (k.clone(), v.clone())).collect(),
+                };
+                let prefix = format!("{}.", name);
+                for (key, val) in &prams.data {
+                    if let Some(key) = key.strip_prefix(&prefix) {
+                        leg_prams.data.insert(key.to_string(), val.clone());
+                    }
+                }
+                if let Some(PramVal::Double(balance)) = leg_prams.data.get("balance") {
+                    let balance = *balance;
+                    leg_prams.data.insert("balance".to_string(), PramVal::Double(balance / n));
+                }
+                leg.set_params(leg_prams)?;
+            }
+            Ok(())
+        }
+
+        pub fn set_commission_model(&mut self, name: &str, model: Box<dyn CommissionModel>) -> Result<(), Box<dyn std::error::Error>> {
+            let leg = self.names.iter().position(|n| n == name).ok_or(format!("No leg named {}", name))?;
+            self.legs[leg].set_commission_model(model);
+            Ok(())
+        }
+
+        pub fn balance(&self) -> f64 {
+            self.legs.iter().map(|leg| leg.balance).sum()
+        }
+
+        pub fn init_balance(&self) -> f64 {
+            self.legs.iter().map(|leg| leg.init_balance).sum()
+        }
+
+        pub fn final_balance(&self) -> f64 {
+            self.legs.iter().map(|leg| leg.final_balance).sum()
+        }
+
+        pub fn leg_pnl(&self, leg: usize) -> f64 {
+            self.legs[leg].final_balance - self.legs[leg].init_balance
+        }
+
+        pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+            for leg in self.legs.iter_mut() {
+                leg.run()?;
+            }
+            Ok(())
+        }
+
+        pub fn stats(&self) -> Result<(), Box<dyn std::error::Error>> {
+            println!("...Portfolio Stats...");
+            for (i, name) in self.names.iter().enumerate() {
+                println!("{} PnL: {}", name, self.leg_pnl(i));
+                println!("{} Commission: {}", name, self.legs[i].total_commission);
+                println!("{} Slippage: {}", name, self.legs[i].total_slippage);
+            }
+            let pnl = self.final_balance() - self.init_balance();
+            let pl = if pnl > 0.0 { "Profit" } else { "Loss" };
+            println!("Combined {}: {}", pl, pnl);
+            println!("Combined Return: {}", pnl / self.init_balance() * 100.0);
+            Ok(())
+        }
+    }
+}
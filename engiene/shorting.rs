@@ -0,0 +1,65 @@
+pub mod shorting {
+    #![allow(dead_code)]
+    use csv::Reader;
+    use std::collections::BTreeMap;
+
+    // Annual rate charged on the value of shares borrowed to short
+    pub enum BorrowRate {
+        Fixed(f64),
+        // rate from each listed day ("YYYY-MM-DD") until the next listed day
+        Schedule(BTreeMap<String, f64>),
+    }
+
+    pub struct ShortCosts {
+        pub borrow: BorrowRate,
+        // shares available to borrow from each listed day on; None means always borrowable
+        pub locates: Option<BTreeMap<String, f64>>,
+        // cash dividend per share, keyed by ex-date
+        pub dividends: BTreeMap<String, f64>,
+        // borrow fees accrue per calendar day on this basis
+        pub day_count: f64,
+    }
+
+    impl ShortCosts {
+        pub fn new() -> Self {
+            ShortCosts {
+                borrow: BorrowRate::Fixed(0.0),
+                locates: None,
+                dividends: BTreeMap::new(),
+                day_count: 360.0,
+            }
+        }
+
+        pub fn rate(&self, day: &str) -> f64 {
+            match &self.borrow {
+                BorrowRate::Fixed(rate) => *rate,
+                BorrowRate::Schedule(rates) => rates.range(..=day.to_string()).next_back().map_or(0.0, |(_, r)| *r),
+            }
+        }
+
+        // Days before the first listed one have nothing to borrow
+        pub fn available(&self, day: &str) -> f64 {
+            match &self.locates {
+                None => f64::INFINITY,
+                Some(table) => table.range(..=day.to_string()).next_back().map_or(0.0, |(_, a)| *a),
+            }
+        }
+
+        pub fn dividend(&self, day: &str) -> f64 {
+            self.dividends.get(day).cloned().unwrap_or(0.0)
+        }
+    }
+
+    // Two-column CSV with a header (Date, value), keyed by the day part of the date
+    pub fn read_series(filename: &str) -> Result<BTreeMap<String, f64>, Box<dyn std::error::Error>> {
+        let mut reader = Reader::from_path(filename)?;
+        let mut series = BTreeMap::new();
+        for line in reader.records() {
+            let line = line?;
+            let date = line.get(0).ok_or("Missing Date")?;
+            let value: f64 = line.get(1).ok_or("Missing value")?.parse()?;
+            series.insert(date.get(..10).unwrap_or(date).to_string(), value);
+        }
+        Ok(series)
+    }
+}
@@ -0,0 +1,253 @@
+pub mod slippage {
+    #![allow(dead_code)]
+    use crate::data_reader::data::data::DataFeed;
+    use crate::engiene::commission::commission::get_double;
+    use crate::engiene::engiene::backtest::{PramVal, Prams};
+
+    // Adverse price move per unit for an order of `quantity` filled at `price` on `bar` of
+    // `feed`, for an instrument quoted in steps of `tick_size`. Buys fill at price + slippage
+    // and sells at price - slippage; the engine charges it once, through the fill price.
+    // Models only look at bars before `bar`.
+    pub trait SlippageModel: Send + Sync {
+        fn slippage(&self, feed: &DataFeed, bar: usize, price: f64, quantity: f64, tick_size: f64) -> f64;
+    }
+
+    // Fixed fraction of the price, in basis points
+    pub struct FixedBps(pub f64);
+
+    impl SlippageModel for FixedBps {
+        fn slippage(&self, _feed: &DataFeed, _bar: usize, price: f64, _quantity: f64, _tick_size: f64) -> f64 {
+            price * self.0 / 10_000.0
+        }
+    }
+
+    // Fixed number of the instrument's ticks
+    pub struct FixedTicks(pub f64);
+
+    impl SlippageModel for FixedTicks {
+        fn slippage(&self, _feed: &DataFeed, _bar: usize, _price: f64, _quantity: f64, tick_size: f64) -> f64 {
+            self.0 * tick_size
+        }
+    }
+
+    // Crossing a quoted bid-ask spread, given as a fraction of the price: half of it is paid
+    pub struct Spread(pub f64);
+
+    impl SlippageModel for Spread {
+        fn slippage(&self, _feed: &DataFeed, _bar: usize, price: f64, _quantity: f64, _tick_size: f64) -> f64 {
+            price * self.0 / 2.0
+        }
+    }
+
+    // `multiple` times the mean true range of the `period` bars before the fill bar
+    pub struct Volatility {
+        pub period: usize,
+        pub multiple: f64,
+    }
+
+    impl SlippageModel for Volatility {
+        fn slippage(&self, feed: &DataFeed, bar: usize, _price: f64, _quantity: f64, _tick_size: f64) -> f64 {
+            let start = bar.saturating_sub(self.period).max(1);
+            if start >= bar {
+                return 0.0;
+            }
+            let total: f64 = (start..bar)
+                .map(|j| {
+                    let prev = feed.Close[j - 1];
+                    (feed.High[j] - feed.Low[j]).max((feed.High[j] - prev).abs()).max((feed.Low[j] - prev).abs())
+                })
+                .sum();
+            self.multiple * total / (bar - start) as f64
+        }
+    }
+
+    // Square-root law: price * coefficient * sigma * sqrt(quantity / average volume), with
+    // sigma and average volume measured over the `period` bars before the fill bar
+    pub struct SquareRootImpact {
+        pub coefficient: f64,
+        pub period: usize,
+    }
+
+    impl SlippageModel for SquareRootImpact {
+        fn slippage(&self, feed: &DataFeed, bar: usize, price: f64, quantity: f64, _tick_size: f64) -> f64 {
+            let start = bar.saturating_sub(self.period).max(1);
+            if start + 1 >= bar {
+                return 0.0;
+            }
+            let returns: Vec<f64> = (start..bar).map(|j| (feed.Close[j] / feed.Close[j - 1]).ln()).collect();
+            let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+            let sigma = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() as f64 - 1.0)).sqrt();
+            let volume = feed.Volume[start..bar].iter().sum::<u64>() as f64 / (bar - start) as f64;
+            if volume <= 0.0 {
+                return 0.0;
+            }
+            price * self.coefficient * sigma * (quantity / volume).sqrt()
+        }
+    }
+
+    fn get_period(prams: &Prams) -> Result<usize, Box<dyn std::error::Error>> {
+        match prams.data.get("slippage_period") {
+            Some(PramVal::Int(v)) if *v > 0 => Ok(*v as usize),
+            Some(_) => Err("slippage_period must be a positive integer".into()),
+            None => Ok(20),
+        }
+    }
+
+    // Model named by "slippage_model" ("fixed_bps", "fixed_ticks", "spread", "volatility" or
+    // "square_root"), sized by "slippage" (a fraction of the price), "slippage_ticks",
+    // "slippage_spread", "slippage_multiple" or "impact_coefficient", over "slippage_period"
+    // bars (20 by default). Without "slippage_model", "slippage" alone selects FixedBps.
+    // Fixed ticks are the instrument's `tick_size`, which must be set. None when the
+    // parameters name no model.
+    pub fn from_params(prams: &Prams, tick_size: f64) -> Result<Option<Box<dyn SlippageModel>>, Box<dyn std::error::Error>> {
+        let name = match prams.data.get("slippage_model") {
+            Some(PramVal::Str(name)) => name.to_lowercase(),
+            Some(_) => return Err("slippage_model must be a string".into()),
+            None if prams.data.contains_key("slippage") => "fixed_bps".to_string(),
+            None => return Ok(None),
+        };
+        let model: Box<dyn SlippageModel> = match name.as_str() {
+            "fixed_bps" => Box::new(FixedBps(get_double(prams, "slippage") * 10_000.0)),
+            "fixed_ticks" => {
+                if tick_size <= 0.0 {
+                    return Err("fixed_ticks slippage needs the instrument's tick_size".into());
+                }
+                Box::new(FixedTicks(get_double(prams, "slippage_ticks")))
+            }
+            "spread" => Box::new(Spread(get_double(prams, "slippage_spread"))),
+            "volatility" => Box::new(Volatility {
+                period: get_period(prams)?,
+                multiple: get_double(prams, "slippage_multiple"),
+            }),
+            "square_root" => Box::new(SquareRootImpact {
+                coefficient: get_double(prams, "impact_coefficient"),
+                period: get_period(prams)?,
+            }),
+            _ => return Err(format!("Unknown slippage_model {}", name).into()),
+        };
+        Ok(Some(model))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::collections::HashMap;
+
+        // (high, low, close, volume) per bar, opening at the close
+        fn feed(bars: &[(f64, f64, f64, u64)]) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for (k, &(high, low, close, volume)) in bars.iter().enumerate() {
+                ohlcv.Date.push(format!("2024-01-{:02}", k + 1));
+                ohlcv.Open.push(close);
+                ohlcv.High.push(high);
+                ohlcv.Low.push(low);
+                ohlcv.Close.push(close);
+                ohlcv.Adj_Close.push(close);
+                ohlcv.Volume.push(volume);
+            }
+            ohlcv
+        }
+
+        fn prams(pairs: &[(&str, PramVal)]) -> Prams {
+            Prams {
+                data: pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect::<HashMap<_, _>>(),
+            }
+        }
+
+        #[test]
+        fn fixed_models_scale_with_price_or_tick() {
+            let ohlcv = feed(&[(200.0, 200.0, 200.0, 1000)]);
+            assert!((FixedBps(5.0).slippage(&ohlcv, 0, 200.0, 10.0, 0.0) - 0.1).abs() < 1e-12);
+            assert!((Spread(0.001).slippage(&ohlcv, 0, 200.0, 10.0, 0.0) - 0.1).abs() < 1e-12);
+            assert_eq!(FixedTicks(2.0).slippage(&ohlcv, 0, 200.0, 10.0, 0.25), 0.5);
+        }
+
+        #[test]
+        fn volatility_is_the_mean_true_range_before_the_fill_bar() {
+            // true ranges 3, 3 and, gapping up from 102, 8
+            let ohlcv = feed(&[
+                (100.0, 100.0, 100.0, 1000),
+                (102.0, 99.0, 101.0, 1000),
+                (103.0, 100.0, 102.0, 1000),
+                (110.0, 106.0, 108.0, 1000),
+                (150.0, 50.0, 108.0, 1000),
+            ]);
+            let model = Volatility { period: 3, multiple: 0.5 };
+            assert!((model.slippage(&ohlcv, 4, 108.0, 1.0, 0.0) - 7.0 / 3.0).abs() < 1e-12);
+            let model = Volatility { period: 2, multiple: 0.5 };
+            assert!((model.slippage(&ohlcv, 4, 108.0, 1.0, 0.0) - 2.75).abs() < 1e-12);
+            // no prior bar with a previous close
+            assert_eq!(model.slippage(&ohlcv, 1, 101.0, 1.0, 0.0), 0.0);
+        }
+
+        #[test]
+        fn square_root_impact_grows_with_the_share_of_volume() {
+            let ohlcv = feed(&[
+                (100.0, 100.0, 100.0, 1000),
+                (110.0, 110.0, 110.0, 1000),
+                (99.0, 99.0, 99.0, 3000),
+                (99.0, 99.0, 99.0, 1000),
+            ]);
+            let model = SquareRootImpact { coefficient: 0.1, period: 2 };
+            // sample deviation of the two log returns, and 500 of an average 2000 traded
+            let sigma = (1.1f64.ln() - 0.9f64.ln()).abs() / 2f64.sqrt();
+            let expected = 99.0 * 0.1 * sigma * 0.5;
+            assert!((model.slippage(&ohlcv, 3, 99.0, 500.0, 0.0) - expected).abs() < 1e-12);
+            assert!((model.slippage(&ohlcv, 3, 99.0, 2000.0, 0.0) - 2.0 * expected).abs() < 1e-12);
+            // one return has no deviation
+            assert_eq!(model.slippage(&ohlcv, 2, 99.0, 500.0, 0.0), 0.0);
+        }
+
+        #[test]
+        fn models_are_read_from_params() {
+            let ohlcv = feed(&[(100.0, 100.0, 100.0, 1000), (102.0, 98.0, 100.0, 1000), (100.0, 100.0, 100.0, 1000)]);
+            assert!(from_params(&prams(&[]), 0.0).unwrap().is_none());
+
+            let model = from_params(&prams(&[("slippage", PramVal::Double(0.0005))]), 0.0).unwrap().unwrap();
+            assert!((model.slippage(&ohlcv, 2, 100.0, 1.0, 0.0) - 0.05).abs() < 1e-12);
+
+            let ticks = prams(&[
+                ("slippage_model", PramVal::Str("fixed_ticks".to_string())),
+                ("slippage_ticks", PramVal::Int(3)),
+            ]);
+            assert!(from_params(&ticks, 0.0).is_err());
+            let model = from_params(&ticks, 0.25).unwrap().unwrap();
+            assert_eq!(model.slippage(&ohlcv, 2, 100.0, 1.0, 0.25), 0.75);
+
+            let model = from_params(
+                &prams(&[
+                    ("slippage_model", PramVal::Str("Spread".to_string())),
+                    ("slippage_spread", PramVal::Double(0.002)),
+                ]),
+                0.0,
+            )
+            .unwrap()
+            .unwrap();
+            assert!((model.slippage(&ohlcv, 2, 100.0, 1.0, 0.0) - 0.1).abs() < 1e-12);
+
+            let model = from_params(
+                &prams(&[
+                    ("slippage_model", PramVal::Str("volatility".to_string())),
+                    ("slippage_multiple", PramVal::Double(0.5)),
+                    ("slippage_period", PramVal::Int(1)),
+                ]),
+                0.0,
+            )
+            .unwrap()
+            .unwrap();
+            assert_eq!(model.slippage(&ohlcv, 2, 100.0, 1.0, 0.0), 2.0);
+
+            assert!(from_params(&prams(&[("slippage_model", PramVal::Str("square_root".to_string()))]), 0.0).is_ok());
+            assert!(from_params(
+                &prams(&[
+                    ("slippage_model", PramVal::Str("volatility".to_string())),
+                    ("slippage_period", PramVal::Int(0)),
+                ]),
+                0.0
+            )
+            .is_err());
+            assert!(from_params(&prams(&[("slippage_model", PramVal::Str("random".to_string()))]), 0.0).is_err());
+            assert!(from_params(&prams(&[("slippage_model", PramVal::Double(1.0))]), 0.0).is_err());
+        }
+    }
+}
@@ -1,4 +1,64 @@
 use crate::data_reader::data::data::DataFeed;
+
+// Named like the indicator methods
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaType {
+    SMA,
+    EMA,
+    WMA,
+    DEMA,
+    TEMA,
+    HMA,
+    KAMA,
+    ZLEMA,
+    VWMA,
+}
+
+// (upper, middle, lower)
+pub type Bands = (Vec<f64>, Vec<f64>, Vec<f64>);
+// (adx, +di, -di)
+pub type Directional = (Vec<f64>, Vec<f64>, Vec<f64>);
+// (tenkan, kijun, senkou_a, senkou_b, chikou)
+pub type Ichimoku = (Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>, Vec<f64>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VolEstimator {
+    CloseToClose,
+    Parkinson,
+    GarmanKlass,
+    YangZhang,
+}
+
+impl MaType {
+    pub fn from_name(name: &str) -> Option<MaType> {
+        match name.to_uppercase().as_str() {
+            "SMA" => Some(MaType::SMA),
+            "EMA" => Some(MaType::EMA),
+            "WMA" => Some(MaType::WMA),
+            "DEMA" => Some(MaType::DEMA),
+            "TEMA" => Some(MaType::TEMA),
+            "HMA" => Some(MaType::HMA),
+            "KAMA" => Some(MaType::KAMA),
+            "ZLEMA" => Some(MaType::ZLEMA),
+            "VWMA" => Some(MaType::VWMA),
+            _ => None,
+        }
+    }
+
+    // Index of the first valid value for an average of `period` bars
+    pub fn lookback(&self, period: usize) -> usize {
+        let p = period.max(1);
+        match self {
+            MaType::SMA | MaType::EMA | MaType::WMA | MaType::KAMA | MaType::VWMA => p - 1,
+            MaType::DEMA => 2 * (p - 1),
+            MaType::TEMA => 3 * (p - 1),
+            MaType::HMA => p - 1 + ((p as f64).sqrt() as usize).max(1) - 1,
+            MaType::ZLEMA => (p - 1) / 2 + p - 1,
+        }
+    }
+}
+
 pub struct Indicators {
     pub ohlcv: DataFeed,
 } 
@@ -46,53 +106,68 @@ impl Indicators {
         Ok(ema)
     }
 
-    fn _MACD(&mut self, fast: i64, slow: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    fn _MACD(&mut self, fast: i64, slow: i64, ma_type: MaType) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
         let ohlcv = self.ohlcv.get_ohlcv();
         let close = &ohlcv.Close;
         let len = close.len();
     
-        let ema_fast = self.EMA(fast)?;
-        let ema_slow = self.EMA(slow)?;
-    
-        if ema_fast.len() != len || ema_slow.len() != len {
-            return Err("EMA lengths do not match OHLCV length".into());
+        if fast <= 0 || slow <= 0 {
+            return Err("Invalid MACD period".into());
+        }
+        let start = ma_type.lookback(fast as usize).max(ma_type.lookback(slow as usize));
+        if start >= len {
+            return Err("Period exceeds data length".into());
         }
+
+        // both averages are seeded on the same bar, as TA-Lib does
+        let ma_fast = self.ma_from_vec(ma_type, close, fast as usize, start - ma_type.lookback(fast as usize))?;
+        let ma_slow = self.ma_from_vec(ma_type, close, slow as usize, start - ma_type.lookback(slow as usize))?;
     
-        let macd: Vec<f64> = ema_fast.iter()
-            .zip(ema_slow.iter())
-            .map(|(short, long)| short - long)
-            .collect();
+        if ma_fast.len() != len || ma_slow.len() != len {
+            return Err("MA lengths do not match OHLCV length".into());
+        }
+
+        let mut macd = vec![0.0; len];
+        for i in start..len {
+            macd[i] = ma_fast[i] - ma_slow[i];
+        }
     
         Ok(macd)
     }
 
-    fn _SIGNAL(&mut self, period: i64, fast: i64, slow: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
-        let macd_line = self._MACD(fast, slow)?; 
-        let signal_line = Self::ema_from_vec(&macd_line, period)?;
+    fn _SIGNAL(&mut self, period: i64, fast: i64, slow: i64, ma_type: MaType) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let macd_line = self._MACD(fast, slow, ma_type)?; 
+        let start = ma_type.lookback(fast as usize).max(ma_type.lookback(slow as usize));
+        let signal_line = self.ma_from_vec(ma_type, &macd_line, period as usize, start)?;
         Ok(signal_line)
     }
 
     fn ema_from_vec(data: &Vec<f64>, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
-        if data.len() < period as usize {
+        Self::ema_from_offset(data, period as usize, 0)
+    }
+
+    // EMA of data[offset..], seeded with the SMA of its first `period` values
+    fn ema_from_offset(data: &[f64], period: usize, offset: usize) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        if period == 0 || data.len() < offset + period {
             return Err("Not enough data to compute EMA".into());
         }
         let mut ema = vec![0.0; data.len()];
         let k = 2.0 / (period as f64 + 1.0);
         
         // SMA for initial EMA value
-        let sma: f64 = data[..period as usize].iter().sum::<f64>() / period as f64;
-        ema[period as usize - 1] = sma;
+        let sma: f64 = data[offset..offset + period].iter().sum::<f64>() / period as f64;
+        ema[offset + period - 1] = sma;
         
-        for i in period as usize..data.len() {
+        for i in offset + period..data.len() {
             ema[i] = (data[i] - ema[i - 1]) * k + ema[i - 1];
         }
         
         Ok(ema)
     }
 
-    pub fn MACD(&mut self, period: i64, fast: i64, slow: i64) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
-        let macd = self._MACD(fast, slow)?;
-        let signal = self._SIGNAL(period, fast, slow)?;
+    pub fn MACD(&mut self, period: i64, fast: i64, slow: i64, ma_type: MaType) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+        let macd = self._MACD(fast, slow, ma_type)?;
+        let signal = self._SIGNAL(period, fast, slow, ma_type)?;
         Ok((macd, signal))
     }
 
@@ -102,8 +177,13 @@ pub fn ATR(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error
     let high = &ohlcv.High;
     let low = &ohlcv.Low;
     let len = close.len();
+    if period <= 0 || period as usize >= len {
+        return Err("Period exceeds data length".into());
+    }
     let mut atr = vec![0.0; len];
     
+    // bar 0 has no previous close, so like TA-Lib the first ATR sits at index `period`
+    // and is the mean of TR[1..=period]
     let mut tr = vec![0.0; len];
     for i in 1..len {
         let prev_close = close[i - 1];
@@ -131,26 +211,24 @@ pub fn ATR(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error
     Ok(atr)
 }
 
-    pub fn BBANDS(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    pub fn BBANDS(&mut self, period: i64, ma_type: MaType) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
         let data = self.ohlcv.get_ohlcv().Close;
-        let sma = self.SMA(period)?;
+        let sma = self.MA(ma_type, period)?;
         let mut bbands = vec![0.0; self.ohlcv.get_ohlcv().Date.len()];
         let mut stddev = vec![0.0; self.ohlcv.get_ohlcv().Date.len()];
 
-        for i in period..data.len() as i64 {
+        for i in (period - 1)..data.len() as i64 {
             let mut sum = 0.0;
-            for j in (i - period)..i {
+            for j in (i + 1 - period)..=i {
                 let diff = data[j as usize] - sma[i as usize];
                 sum += diff * diff;
             }
             stddev[i as usize] = (sum / period as f64).sqrt();
         }
     
+        // a flat window has no band width and stays at 0.0
         for i in 0..data.len() {
-            if stddev[i] == 0.0 {
-                println!("Warning: Standard deviation is zero at index {}, setting BBANDS to 0.0", i);
-                bbands[i] = 0.0;
-            } else {
+            if stddev[i] != 0.0 {
                 bbands[i] = (data[i] - sma[i]) / (2.0 * stddev[i]);
             }
         }
@@ -158,28 +236,23 @@ pub fn ATR(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error
         Ok(bbands)
     }
 
+    // Fast %K over the last `period` bars including the current one (TA-Lib STOCHF fastk)
     pub fn STOCHASTIC(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
-        let data = self.ohlcv.get_ohlcv().Close;
-        let high = &self.ohlcv.get_ohlcv().High;
-        let low = &self.ohlcv.get_ohlcv().Low;
-        let mut stochastic = vec![0.0; self.ohlcv.get_ohlcv().Date.len()];
-        let mut min = 0.0;
-        let mut max = 0.0;
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let data = &ohlcv.Close;
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let p = period as usize;
+        if period <= 0 || p > data.len() {
+            return Err("Period exceeds data length".into());
+        }
+        let mut stochastic = vec![0.0; data.len()];
         
-        for i in period..data.len() as i64 {
-            min = low[(i-period) as usize];
-            max = high[(i-period) as usize];
-
-            for j in (i-period)..i {
-                if low[j as usize] < min {
-                    min = low[j as usize];
-                }
-                if high[j as usize] > max {
-                    max = high[j as usize];
-                }
-            }
-            stochastic[i as usize] = if max != min {
-                (data[i as usize] - min) / (max - min) * 100.0
+        for i in (p - 1)..data.len() {
+            let (_, max) = Self::highest_from_vec(high, i + 1 - p, i);
+            let (_, min) = Self::lowest_from_vec(low, i + 1 - p, i);
+            stochastic[i] = if max != min {
+                (data[i] - min) / (max - min) * 100.0
             } else {
                 0.0
             };
@@ -187,29 +260,702 @@ pub fn ATR(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error
         Ok(stochastic)
     }
 
+    // Williams %R on the -100..0 scale over the last `period` bars including the current one
     pub fn WILLIAMS(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
-        let data = self.ohlcv.get_ohlcv().Close;
-        let mut williams = vec![0.0; self.ohlcv.get_ohlcv().Date.len()];
-        let mut min = 0f64;
-        let mut max = 0f64;
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let data = &ohlcv.Close;
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let p = period as usize;
+        if period <= 0 || p > data.len() {
+            return Err("Period exceeds data length".into());
+        }
+        let mut williams = vec![0.0; data.len()];
         
-        for i in period..data.len() as i64 {
-            min = data[(i-period) as usize];
-            max = data[(i-period) as usize];
-
-            for j in (i-period)..i {
-                if data[j as usize] < min {
-                    min = data[j as usize];
-                } else if data[j as usize] > max {
-                    max = data[j as usize];
-                }
-            }
-            williams[i as usize] = if max != min {
-                (max - data[i as usize]) / (max - min)
+        for i in (p - 1)..data.len() {
+            let (_, max) = Self::highest_from_vec(high, i + 1 - p, i);
+            let (_, min) = Self::lowest_from_vec(low, i + 1 - p, i);
+            williams[i] = if max != min {
+                -100.0 * (max - data[i]) / (max - min)
             } else {
                 0.0
             };
         }
         Ok(williams)
     }
+
+    fn highest_from_vec(data: &[f64], start: usize, end: usize) -> (usize, f64) {
+        let mut idx = start;
+        let mut max = data[start];
+        for (j, &value) in data[start..=end].iter().enumerate() {
+            if value >= max {
+                max = value;
+                idx = start + j;
+            }
+        }
+        (idx, max)
+    }
+
+    fn lowest_from_vec(data: &[f64], start: usize, end: usize) -> (usize, f64) {
+        let mut idx = start;
+        let mut min = data[start];
+        for (j, &value) in data[start..=end].iter().enumerate() {
+            if value <= min {
+                min = value;
+                idx = start + j;
+            }
+        }
+        (idx, min)
+    }
+
+    // Returns (adx, +di, -di) using Wilder smoothing
+    pub fn ADX(&mut self, period: i64) -> Result<Directional, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let close = &ohlcv.Close;
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = close.len();
+        let p = period as usize;
+        if period <= 0 || 2 * p >= len {
+            return Err("Period exceeds data length".into());
+        }
+        let mut adx = vec![0.0; len];
+        let mut plus_di = vec![0.0; len];
+        let mut minus_di = vec![0.0; len];
+        let mut dx = vec![0.0; len];
+
+        let mut tr = vec![0.0; len];
+        let mut plus_dm = vec![0.0; len];
+        let mut minus_dm = vec![0.0; len];
+        for i in 1..len {
+            let up = high[i] - high[i - 1];
+            let down = low[i - 1] - low[i];
+            plus_dm[i] = if up > down && up > 0.0 { up } else { 0.0 };
+            minus_dm[i] = if down > up && down > 0.0 { down } else { 0.0 };
+            tr[i] = f64::max(
+                high[i] - low[i],
+                f64::max(
+                    (high[i] - close[i - 1]).abs(),
+                    (low[i] - close[i - 1]).abs()
+                )
+            );
+        }
+
+        let mut sm_tr = 0.0;
+        let mut sm_plus = 0.0;
+        let mut sm_minus = 0.0;
+        // like TA-Lib, sum the first period - 1 bars and smooth from bar `period` on
+        for i in 1..len {
+            if i < p {
+                sm_tr += tr[i];
+                sm_plus += plus_dm[i];
+                sm_minus += minus_dm[i];
+                continue;
+            } else {
+                sm_tr = sm_tr - sm_tr / period as f64 + tr[i];
+                sm_plus = sm_plus - sm_plus / period as f64 + plus_dm[i];
+                sm_minus = sm_minus - sm_minus / period as f64 + minus_dm[i];
+            }
+            if sm_tr != 0.0 {
+                plus_di[i] = 100.0 * sm_plus / sm_tr;
+                minus_di[i] = 100.0 * sm_minus / sm_tr;
+            }
+            let di_sum = plus_di[i] + minus_di[i];
+            dx[i] = if di_sum != 0.0 {
+                100.0 * (plus_di[i] - minus_di[i]).abs() / di_sum
+            } else {
+                0.0
+            };
+        }
+
+        // first ADX is the mean of the first `period` DX values
+        let seed = 2 * p - 1;
+        adx[seed] = dx[p..=seed].iter().sum::<f64>() / period as f64;
+        for i in (seed + 1)..len {
+            adx[i] = (adx[i - 1] * (period as f64 - 1.0) + dx[i]) / period as f64;
+        }
+
+        Ok((adx, plus_di, minus_di))
+    }
+
+    // Wilder's Parabolic SAR with TA-Lib's start-up: direction from the first bar's
+    // directional movement, SAR at the prior extreme
+    pub fn PSAR(&mut self, step: f64, max_step: f64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = high.len();
+        if len < 2 {
+            return Err("Not enough data to compute PSAR".into());
+        }
+        if step <= 0.0 || max_step < step {
+            return Err("Invalid PSAR step".into());
+        }
+        let mut sar = vec![0.0; len];
+
+        let up = high[1] - high[0];
+        let down = low[0] - low[1];
+        let mut is_long = !(down > 0.0 && up < down);
+        let mut af = step;
+        let (mut ep, mut sar_val) = if is_long { (high[1], low[0]) } else { (low[1], high[0]) };
+        let mut new_high = high[1];
+        let mut new_low = low[1];
+
+        for i in 1..len {
+            let prev_high = new_high;
+            let prev_low = new_low;
+            new_high = high[i];
+            new_low = low[i];
+            if is_long {
+                if new_low <= sar_val {
+                    is_long = false;
+                    sar_val = ep.max(prev_high).max(new_high);
+                    sar[i] = sar_val;
+                    af = step;
+                    ep = new_low;
+                    sar_val = (sar_val + af * (ep - sar_val)).max(prev_high).max(new_high);
+                } else {
+                    sar[i] = sar_val;
+                    if new_high > ep {
+                        ep = new_high;
+                        af = (af + step).min(max_step);
+                    }
+                    // SAR may never sit above the current or previous low
+                    sar_val = (sar_val + af * (ep - sar_val)).min(prev_low).min(new_low);
+                }
+            } else if new_high >= sar_val {
+                is_long = true;
+                sar_val = ep.min(prev_low).min(new_low);
+                sar[i] = sar_val;
+                af = step;
+                ep = new_high;
+                sar_val = (sar_val + af * (ep - sar_val)).min(prev_low).min(new_low);
+            } else {
+                sar[i] = sar_val;
+                if new_low < ep {
+                    ep = new_low;
+                    af = (af + step).min(max_step);
+                }
+                sar_val = (sar_val + af * (ep - sar_val)).max(prev_high).max(new_high);
+            }
+        }
+        Ok(sar)
+    }
+
+    // Returns (supertrend, direction) where direction is 1.0 for up and -1.0 for down
+    pub fn SUPERTREND(&mut self, period: i64, multiplier: f64) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let close = &ohlcv.Close;
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = close.len();
+        let p = period as usize;
+        if period <= 0 || p >= len {
+            return Err("Period exceeds data length".into());
+        }
+        let atr = self.ATR(period)?;
+        let mut supertrend = vec![0.0; len];
+        let mut direction = vec![0.0; len];
+        let mut upper = vec![0.0; len];
+        let mut lower = vec![0.0; len];
+
+        for i in p..len {
+            let hl2 = (high[i] + low[i]) / 2.0;
+            let basic_upper = hl2 + multiplier * atr[i];
+            let basic_lower = hl2 - multiplier * atr[i];
+
+            if i == p {
+                upper[i] = basic_upper;
+                lower[i] = basic_lower;
+                direction[i] = if close[i] >= hl2 { 1.0 } else { -1.0 };
+            } else {
+                upper[i] = if basic_upper < upper[i - 1] || close[i - 1] > upper[i - 1] {
+                    basic_upper
+                } else {
+                    upper[i - 1]
+                };
+                lower[i] = if basic_lower > lower[i - 1] || close[i - 1] < lower[i - 1] {
+                    basic_lower
+                } else {
+                    lower[i - 1]
+                };
+                direction[i] = if direction[i - 1] < 0.0 && close[i] > upper[i - 1] {
+                    1.0
+                } else if direction[i - 1] > 0.0 && close[i] < lower[i - 1] {
+                    -1.0
+                } else {
+                    direction[i - 1]
+                };
+            }
+            supertrend[i] = if direction[i] > 0.0 { lower[i] } else { upper[i] };
+        }
+        Ok((supertrend, direction))
+    }
+
+    // Returns (tenkan, kijun, senkou_a, senkou_b, chikou).
+    // The senkou spans are displaced forward by `kijun` bars, so the value at index i is
+    // built only from bars up to i - kijun. Chikou is the close displaced back by `kijun`
+    // bars and therefore looks ahead; it is for charting and must not drive signals.
+    pub fn ICHIMOKU(&mut self, tenkan: i64, kijun: i64, senkou: i64) -> Result<Ichimoku, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let close = &ohlcv.Close;
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = close.len();
+        if tenkan <= 0 || kijun <= 0 || senkou <= 0 {
+            return Err("Invalid Ichimoku period".into());
+        }
+        let (t, k, s) = (tenkan as usize, kijun as usize, senkou as usize);
+        if t.max(k).max(s) > len {
+            return Err("Period exceeds data length".into());
+        }
+
+        let midpoint = |period: usize, i: usize| -> f64 {
+            let (_, hh) = Self::highest_from_vec(high, i + 1 - period, i);
+            let (_, ll) = Self::lowest_from_vec(low, i + 1 - period, i);
+            (hh + ll) / 2.0
+        };
+
+        let mut tenkan_sen = vec![0.0; len];
+        let mut kijun_sen = vec![0.0; len];
+        let mut senkou_a = vec![0.0; len];
+        let mut senkou_b = vec![0.0; len];
+        let mut chikou = vec![0.0; len];
+
+        for (i, value) in tenkan_sen.iter_mut().enumerate().skip(t - 1) {
+            *value = midpoint(t, i);
+        }
+        for (i, value) in kijun_sen.iter_mut().enumerate().skip(k - 1) {
+            *value = midpoint(k, i);
+        }
+        for i in (t.max(k) - 1 + k)..len {
+            senkou_a[i] = (tenkan_sen[i - k] + kijun_sen[i - k]) / 2.0;
+        }
+        for (i, value) in senkou_b.iter_mut().enumerate().skip(s - 1 + k) {
+            *value = midpoint(s, i - k);
+        }
+        chikou[..len - k].copy_from_slice(&close[k..]);
+
+        Ok((tenkan_sen, kijun_sen, senkou_a, senkou_b, chikou))
+    }
+
+    // Returns (aroon_up, aroon_down) over a window of period + 1 bars
+    pub fn AROON(&mut self, period: i64) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = high.len();
+        let p = period as usize;
+        if period <= 0 || p >= len {
+            return Err("Period exceeds data length".into());
+        }
+        let mut aroon_up = vec![0.0; len];
+        let mut aroon_down = vec![0.0; len];
+
+        for i in p..len {
+            let (hi_idx, _) = Self::highest_from_vec(high, i - p, i);
+            let (lo_idx, _) = Self::lowest_from_vec(low, i - p, i);
+            aroon_up[i] = 100.0 * (p - (i - hi_idx)) as f64 / period as f64;
+            aroon_down[i] = 100.0 * (p - (i - lo_idx)) as f64 / period as f64;
+        }
+        Ok((aroon_up, aroon_down))
+    }
+
+    pub fn OBV(&mut self) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let close = &ohlcv.Close;
+        let volume = &ohlcv.Volume;
+        let len = close.len();
+        if len == 0 {
+            return Err("No data available".into());
+        }
+        let mut obv = vec![0.0; len];
+        obv[0] = volume[0] as f64;
+
+        for i in 1..len {
+            obv[i] = if close[i] > close[i - 1] {
+                obv[i - 1] + volume[i] as f64
+            } else if close[i] < close[i - 1] {
+                obv[i - 1] - volume[i] as f64
+            } else {
+                obv[i - 1]
+            };
+        }
+        Ok(obv)
+    }
+
+    fn vwap_from(&self, start: usize, reset_on_session: bool) -> Vec<f64> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let len = ohlcv.Close.len();
+        let mut vwap = vec![0.0; len];
+        let mut pv = 0.0;
+        let mut vol = 0.0;
+
+        for (i, value) in vwap.iter_mut().enumerate().skip(start) {
+            // a session is a calendar day, taken from the leading YYYY-MM-DD of the date
+            if reset_on_session && i > start && ohlcv.Date[i].get(..10) != ohlcv.Date[i - 1].get(..10) {
+                pv = 0.0;
+                vol = 0.0;
+            }
+            let typical = (ohlcv.High[i] + ohlcv.Low[i] + ohlcv.Close[i]) / 3.0;
+            pv += typical * ohlcv.Volume[i] as f64;
+            vol += ohlcv.Volume[i] as f64;
+            *value = if vol > 0.0 { pv / vol } else { typical };
+        }
+        vwap
+    }
+
+    // Session VWAP, reset at every change of calendar day
+    pub fn VWAP(&mut self) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        if self.ohlcv.Date.is_empty() {
+            return Err("No data available".into());
+        }
+        Ok(self.vwap_from(0, true))
+    }
+
+    // VWAP accumulated from the anchor bar onwards; bars before the anchor are 0.0
+    pub fn AVWAP(&mut self, anchor: usize) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        if anchor >= self.ohlcv.Date.len() {
+            return Err("Anchor exceeds data length".into());
+        }
+        Ok(self.vwap_from(anchor, false))
+    }
+
+    pub fn AVWAP_DATE(&mut self, date: &str) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let anchor = self.ohlcv.Date.iter().position(|d| d == date).ok_or("Anchor date not found")?;
+        self.AVWAP(anchor)
+    }
+
+    fn money_flow_volume(high: f64, low: f64, close: f64, volume: f64) -> f64 {
+        if high == low {
+            return 0.0;
+        }
+        ((close - low) - (high - close)) / (high - low) * volume
+    }
+
+    pub fn AD(&mut self) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let len = ohlcv.Close.len();
+        if len == 0 {
+            return Err("No data available".into());
+        }
+        let mut ad = vec![0.0; len];
+        let mut sum = 0.0;
+
+        for (i, value) in ad.iter_mut().enumerate() {
+            sum += Self::money_flow_volume(ohlcv.High[i], ohlcv.Low[i], ohlcv.Close[i], ohlcv.Volume[i] as f64);
+            *value = sum;
+        }
+        Ok(ad)
+    }
+
+    pub fn CMF(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let len = ohlcv.Close.len();
+        let p = period as usize;
+        if period <= 0 || p > len {
+            return Err("Period exceeds data length".into());
+        }
+        let mut cmf = vec![0.0; len];
+        let mfv: Vec<f64> = (0..len)
+            .map(|i| Self::money_flow_volume(ohlcv.High[i], ohlcv.Low[i], ohlcv.Close[i], ohlcv.Volume[i] as f64))
+            .collect();
+
+        for i in (p - 1)..len {
+            let flow: f64 = mfv[(i + 1 - p)..=i].iter().sum();
+            let vol: f64 = ohlcv.Volume[(i + 1 - p)..=i].iter().map(|v| *v as f64).sum();
+            cmf[i] = if vol > 0.0 { flow / vol } else { 0.0 };
+        }
+        Ok(cmf)
+    }
+
+    // Chaikin oscillator: EMA(fast) - EMA(slow) of the accumulation/distribution line.
+    // As in TA-Lib ADOSC both EMAs start from the first A/D value.
+    pub fn CHAIKIN(&mut self, fast: i64, slow: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let ad = self.AD()?;
+        if fast <= 0 || slow <= 0 || fast.max(slow) as usize > ad.len() {
+            return Err("Period exceeds data length".into());
+        }
+        let k_fast = 2.0 / (fast as f64 + 1.0);
+        let k_slow = 2.0 / (slow as f64 + 1.0);
+        let mut ema_fast = ad[0];
+        let mut ema_slow = ad[0];
+        let mut chaikin = vec![0.0; ad.len()];
+
+        for i in 1..ad.len() {
+            ema_fast += (ad[i] - ema_fast) * k_fast;
+            ema_slow += (ad[i] - ema_slow) * k_slow;
+            if i >= fast.max(slow) as usize - 1 {
+                chaikin[i] = ema_fast - ema_slow;
+            }
+        }
+        Ok(chaikin)
+    }
+
+    pub fn MA(&mut self, ma_type: MaType, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let data = self.ohlcv.get_ohlcv().Close;
+        if period <= 0 || ma_type.lookback(period as usize) >= data.len() {
+            return Err("Period exceeds data length".into());
+        }
+        self.ma_from_vec(ma_type, &data, period as usize, 0)
+    }
+
+    pub fn WMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::WMA, period)
+    }
+
+    pub fn DEMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::DEMA, period)
+    }
+
+    pub fn TEMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::TEMA, period)
+    }
+
+    pub fn HMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::HMA, period)
+    }
+
+    pub fn KAMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::KAMA, period)
+    }
+
+    pub fn ZLEMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::ZLEMA, period)
+    }
+
+    pub fn VWMA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        self.MA(MaType::VWMA, period)
+    }
+
+    // Moving average of data[offset..]; values before the lookback are 0.0.
+    // VWMA weights by the feed volume, so `data` must be aligned with the feed.
+    fn ma_from_vec(&self, ma_type: MaType, data: &[f64], period: usize, offset: usize) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let len = data.len();
+        if period == 0 || offset + ma_type.lookback(period) >= len {
+            return Err("Not enough data to compute moving average".into());
+        }
+        let p = period;
+        match ma_type {
+            MaType::SMA => Ok(Self::sma_from_vec(data, p, offset)),
+            MaType::EMA => Self::ema_from_offset(data, p, offset),
+            MaType::WMA => Ok(Self::wma_from_vec(data, p, offset)),
+            MaType::DEMA => {
+                let ema1 = Self::ema_from_offset(data, p, offset)?;
+                let ema2 = Self::ema_from_offset(&ema1, p, offset + p - 1)?;
+                let mut dema = vec![0.0; len];
+                for i in (offset + 2 * (p - 1))..len {
+                    dema[i] = 2.0 * ema1[i] - ema2[i];
+                }
+                Ok(dema)
+            }
+            MaType::TEMA => {
+                let ema1 = Self::ema_from_offset(data, p, offset)?;
+                let ema2 = Self::ema_from_offset(&ema1, p, offset + p - 1)?;
+                let ema3 = Self::ema_from_offset(&ema2, p, offset + 2 * (p - 1))?;
+                let mut tema = vec![0.0; len];
+                for i in (offset + 3 * (p - 1))..len {
+                    tema[i] = 3.0 * ema1[i] - 3.0 * ema2[i] + ema3[i];
+                }
+                Ok(tema)
+            }
+            MaType::HMA => {
+                let half = (p / 2).max(1);
+                let sqrt = ((p as f64).sqrt() as usize).max(1);
+                let wma_half = Self::wma_from_vec(data, half, offset);
+                let wma_full = Self::wma_from_vec(data, p, offset);
+                let mut raw = vec![0.0; len];
+                for i in (offset + p - 1)..len {
+                    raw[i] = 2.0 * wma_half[i] - wma_full[i];
+                }
+                Ok(Self::wma_from_vec(&raw, sqrt, offset + p - 1))
+            }
+            MaType::KAMA => {
+                // efficiency ratio scaled between the 2-bar and 30-bar EMA constants
+                let fast_sc = 2.0 / (2.0 + 1.0);
+                let slow_sc = 2.0 / (30.0 + 1.0);
+                let mut kama = vec![0.0; len];
+                let seed = offset + p - 1;
+                kama[seed] = data[seed];
+                for i in (seed + 1)..len {
+                    let change = if i >= offset + p { (data[i] - data[i - p]).abs() } else { (data[i] - data[offset]).abs() };
+                    let first = if i >= offset + p { i + 1 - p } else { offset + 1 };
+                    let volatility: f64 = (first..=i).map(|j| (data[j] - data[j - 1]).abs()).sum();
+                    let er = if volatility != 0.0 { change / volatility } else { 1.0 };
+                    let sc = (er * (fast_sc - slow_sc) + slow_sc).powi(2);
+                    kama[i] = kama[i - 1] + sc * (data[i] - kama[i - 1]);
+                }
+                Ok(kama)
+            }
+            MaType::ZLEMA => {
+                let lag = (p - 1) / 2;
+                let mut adjusted = vec![0.0; len];
+                for i in (offset + lag)..len {
+                    adjusted[i] = 2.0 * data[i] - data[i - lag];
+                }
+                Self::ema_from_offset(&adjusted, p, offset + lag)
+            }
+            MaType::VWMA => {
+                let volume = &self.ohlcv.Volume;
+                if volume.len() != len {
+                    return Err("VWMA input is not aligned with volume".into());
+                }
+                let mut vwma = vec![0.0; len];
+                for (i, value) in vwma.iter_mut().enumerate().skip(offset + p - 1) {
+                    let mut pv = 0.0;
+                    let mut vol = 0.0;
+                    for (x, v) in data[(i + 1 - p)..=i].iter().zip(&volume[(i + 1 - p)..=i]) {
+                        pv += x * *v as f64;
+                        vol += *v as f64;
+                    }
+                    *value = if vol > 0.0 { pv / vol } else { 0.0 };
+                }
+                Ok(vwma)
+            }
+        }
+    }
+
+    fn sma_from_vec(data: &[f64], period: usize, offset: usize) -> Vec<f64> {
+        let mut sma = vec![0.0; data.len()];
+        let mut sum: f64 = data[offset..offset + period].iter().sum();
+        sma[offset + period - 1] = sum / period as f64;
+        for i in (offset + period)..data.len() {
+            sum += data[i] - data[i - period];
+            sma[i] = sum / period as f64;
+        }
+        sma
+    }
+
+    fn wma_from_vec(data: &[f64], period: usize, offset: usize) -> Vec<f64> {
+        let mut wma = vec![0.0; data.len()];
+        let denom = (period * (period + 1)) as f64 / 2.0;
+        for (i, value) in wma.iter_mut().enumerate().skip(offset + period - 1) {
+            let sum: f64 = data[(i + 1 - period)..=i].iter().enumerate().map(|(w, x)| x * (w + 1) as f64).sum();
+            *value = sum / denom;
+        }
+        wma
+    }
+
+    // Returns (upper, middle, lower) with an EMA midline and ATR-scaled bands
+    pub fn KELTNER(&mut self, period: i64, atr_period: i64, multiplier: f64) -> Result<Bands, Box<dyn std::error::Error>> {
+        let len = self.ohlcv.Close.len();
+        if period <= 0 || atr_period <= 0 || period as usize > len || atr_period as usize >= len {
+            return Err("Period exceeds data length".into());
+        }
+        let middle = self.EMA(period)?;
+        let atr = self.ATR(atr_period)?;
+        let mut upper = vec![0.0; len];
+        let mut lower = vec![0.0; len];
+
+        for i in (period as usize - 1).max(atr_period as usize)..len {
+            upper[i] = middle[i] + multiplier * atr[i];
+            lower[i] = middle[i] - multiplier * atr[i];
+        }
+        Ok((upper, middle, lower))
+    }
+
+    // Returns (upper, middle, lower) from the highest high and lowest low of the last `period` bars
+    pub fn DONCHIAN(&mut self, period: i64) -> Result<Bands, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = high.len();
+        let p = period as usize;
+        if period <= 0 || p > len {
+            return Err("Period exceeds data length".into());
+        }
+        let mut upper = vec![0.0; len];
+        let mut middle = vec![0.0; len];
+        let mut lower = vec![0.0; len];
+
+        for i in (p - 1)..len {
+            let (_, hh) = Self::highest_from_vec(high, i + 1 - p, i);
+            let (_, ll) = Self::lowest_from_vec(low, i + 1 - p, i);
+            upper[i] = hh;
+            lower[i] = ll;
+            middle[i] = (hh + ll) / 2.0;
+        }
+        Ok((upper, middle, lower))
+    }
+
+    // Rolling historical volatility over `period` bars, annualised by sqrt(periods_per_year)
+    pub fn HV(&mut self, period: i64, estimator: VolEstimator, periods_per_year: f64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let open = &ohlcv.Open;
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let close = &ohlcv.Close;
+        let len = close.len();
+        let p = period as usize;
+        if period < 2 || p >= len {
+            return Err("Period exceeds data length".into());
+        }
+        let mut hv = vec![0.0; len];
+        let n = period as f64;
+
+        let sample_var = |xs: &Vec<f64>| -> f64 {
+            let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+            xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (xs.len() as f64 - 1.0)
+        };
+
+        for (i, value) in hv.iter_mut().enumerate().skip(p) {
+            let window = (i + 1 - p)..=i;
+            let var = match estimator {
+                VolEstimator::CloseToClose => {
+                    let returns: Vec<f64> = window.map(|j| (close[j] / close[j - 1]).ln()).collect();
+                    sample_var(&returns)
+                }
+                VolEstimator::Parkinson => {
+                    let sum: f64 = window.map(|j| (high[j] / low[j]).ln().powi(2)).sum();
+                    sum / (4.0 * n * std::f64::consts::LN_2)
+                }
+                VolEstimator::GarmanKlass => {
+                    let sum: f64 = window
+                        .map(|j| {
+                            0.5 * (high[j] / low[j]).ln().powi(2)
+                                - (2.0 * std::f64::consts::LN_2 - 1.0) * (close[j] / open[j]).ln().powi(2)
+                        })
+                        .sum();
+                    sum / n
+                }
+                VolEstimator::YangZhang => {
+                    let overnight: Vec<f64> = window.clone().map(|j| (open[j] / close[j - 1]).ln()).collect();
+                    let intraday: Vec<f64> = window.clone().map(|j| (close[j] / open[j]).ln()).collect();
+                    let rogers_satchell: f64 = window
+                        .map(|j| {
+                            (high[j] / close[j]).ln() * (high[j] / open[j]).ln()
+                                + (low[j] / close[j]).ln() * (low[j] / open[j]).ln()
+                        })
+                        .sum::<f64>() / n;
+                    let k = 0.34 / (1.34 + (n + 1.0) / (n - 1.0));
+                    sample_var(&overnight) + k * sample_var(&intraday) + (1.0 - k) * rogers_satchell
+                }
+            };
+            *value = (var.max(0.0) * periods_per_year).sqrt();
+        }
+        Ok(hv)
+    }
+
+    // Returns (long_exit, short_exit): the extreme of the last `period` bars offset by ATR
+    pub fn CHANDELIER(&mut self, period: i64, multiplier: f64) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+        let ohlcv = self.ohlcv.get_ohlcv();
+        let high = &ohlcv.High;
+        let low = &ohlcv.Low;
+        let len = high.len();
+        let p = period as usize;
+        if period <= 0 || p >= len {
+            return Err("Period exceeds data length".into());
+        }
+        let atr = self.ATR(period)?;
+        let mut long_exit = vec![0.0; len];
+        let mut short_exit = vec![0.0; len];
+
+        for i in p..len {
+            let (_, hh) = Self::highest_from_vec(high, i + 1 - p, i);
+            let (_, ll) = Self::lowest_from_vec(low, i + 1 - p, i);
+            long_exit[i] = hh - multiplier * atr[i];
+            short_exit[i] = ll + multiplier * atr[i];
+        }
+        Ok((long_exit, short_exit))
+    }
 }
\ No newline at end of file
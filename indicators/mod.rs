@@ -1 +1,4 @@
-pub mod indicators;
\ No newline at end of file
+pub mod indicators;
+pub mod patterns;
+pub mod reference;
+pub mod stats;
\ No newline at end of file
@@ -0,0 +1,426 @@
+use crate::data_reader::data::data::DataFeed;
+
+// bars used for the average body that "long" and "small" bodies are measured against
+const AVG_BODY_BARS: usize = 10;
+// bars used to decide the prevailing trend before a reversal pattern
+const TREND_BARS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    Doji,
+    Hammer,
+    HangingMan,
+    InvertedHammer,
+    ShootingStar,
+    Marubozu,
+    Engulfing,
+    Harami,
+    Piercing,
+    DarkCloudCover,
+    MorningStar,
+    EveningStar,
+    ThreeWhiteSoldiers,
+    ThreeBlackCrows,
+}
+
+pub const ALL_PATTERNS: [Pattern; 14] = [
+    Pattern::Doji,
+    Pattern::Hammer,
+    Pattern::HangingMan,
+    Pattern::InvertedHammer,
+    Pattern::ShootingStar,
+    Pattern::Marubozu,
+    Pattern::Engulfing,
+    Pattern::Harami,
+    Pattern::Piercing,
+    Pattern::DarkCloudCover,
+    Pattern::MorningStar,
+    Pattern::EveningStar,
+    Pattern::ThreeWhiteSoldiers,
+    Pattern::ThreeBlackCrows,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Bullish,
+    Bearish,
+    Neutral,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternSignal {
+    pub pattern: Pattern,
+    pub direction: Direction,
+    // 0.0..=1.0, how cleanly the bars match the pattern
+    pub strength: f64,
+}
+
+pub struct Patterns {
+    pub ohlcv: DataFeed,
+}
+
+impl Patterns {
+    // Every pattern completed on each bar, indexed like the feed
+    pub fn scan(&mut self) -> Result<Vec<Vec<PatternSignal>>, Box<dyn std::error::Error>> {
+        let len = self.ohlcv.Close.len();
+        let mut signals = vec![Vec::new(); len];
+        for (i, bar) in signals.iter_mut().enumerate() {
+            for pattern in ALL_PATTERNS.iter() {
+                if let Some(signal) = self.detect_at(*pattern, i) {
+                    bar.push(signal);
+                }
+            }
+        }
+        Ok(signals)
+    }
+
+    // Per-bar series for one pattern: +strength when bullish, -strength when bearish,
+    // 0.0 otherwise. Neutral patterns (doji) report +strength.
+    pub fn signal(&mut self, pattern: Pattern) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let len = self.ohlcv.Close.len();
+        if len == 0 {
+            return Err("No data available".into());
+        }
+        let mut series = vec![0.0; len];
+        for (i, value) in series.iter_mut().enumerate() {
+            if let Some(signal) = self.detect_at(pattern, i) {
+                *value = match signal.direction {
+                    Direction::Bearish => -signal.strength,
+                    _ => signal.strength,
+                };
+            }
+        }
+        Ok(series)
+    }
+
+    fn body(&self, i: usize) -> f64 {
+        (self.ohlcv.Close[i] - self.ohlcv.Open[i]).abs()
+    }
+
+    fn range(&self, i: usize) -> f64 {
+        self.ohlcv.High[i] - self.ohlcv.Low[i]
+    }
+
+    fn upper_shadow(&self, i: usize) -> f64 {
+        self.ohlcv.High[i] - self.ohlcv.Open[i].max(self.ohlcv.Close[i])
+    }
+
+    fn lower_shadow(&self, i: usize) -> f64 {
+        self.ohlcv.Open[i].min(self.ohlcv.Close[i]) - self.ohlcv.Low[i]
+    }
+
+    fn is_bullish(&self, i: usize) -> bool {
+        self.ohlcv.Close[i] > self.ohlcv.Open[i]
+    }
+
+    fn is_bearish(&self, i: usize) -> bool {
+        self.ohlcv.Close[i] < self.ohlcv.Open[i]
+    }
+
+    fn body_mid(&self, i: usize) -> f64 {
+        (self.ohlcv.Open[i] + self.ohlcv.Close[i]) / 2.0
+    }
+
+    // average body of the AVG_BODY_BARS bars before i
+    fn avg_body(&self, i: usize) -> f64 {
+        let start = i.saturating_sub(AVG_BODY_BARS);
+        if start == i {
+            return self.body(i);
+        }
+        (start..i).map(|j| self.body(j)).sum::<f64>() / (i - start) as f64
+    }
+
+    fn is_long_body(&self, i: usize) -> bool {
+        self.body(i) > self.avg_body(i)
+    }
+
+    fn is_small_body(&self, i: usize) -> bool {
+        self.body(i) < 0.5 * self.avg_body(i)
+    }
+
+    // trend of the closes leading into bar i, excluding bar i itself
+    fn in_downtrend(&self, i: usize) -> bool {
+        i > TREND_BARS && self.ohlcv.Close[i - 1] < self.ohlcv.Close[i - 1 - TREND_BARS]
+    }
+
+    fn in_uptrend(&self, i: usize) -> bool {
+        i > TREND_BARS && self.ohlcv.Close[i - 1] > self.ohlcv.Close[i - 1 - TREND_BARS]
+    }
+
+    fn detect_at(&self, pattern: Pattern, i: usize) -> Option<PatternSignal> {
+        let open = &self.ohlcv.Open;
+        let close = &self.ohlcv.Close;
+        let range = self.range(i);
+        if range <= 0.0 {
+            return None;
+        }
+        let body = self.body(i);
+
+        let found = match pattern {
+            Pattern::Doji => {
+                if body <= 0.1 * range {
+                    Some((Direction::Neutral, 1.0 - body / (0.1 * range)))
+                } else {
+                    None
+                }
+            }
+            Pattern::Hammer | Pattern::HangingMan => {
+                let shape = body > 0.0 && self.lower_shadow(i) >= 2.0 * body && self.upper_shadow(i) <= 0.1 * range;
+                let strength = self.lower_shadow(i) / range;
+                match pattern {
+                    Pattern::Hammer if shape && self.in_downtrend(i) => Some((Direction::Bullish, strength)),
+                    Pattern::HangingMan if shape && self.in_uptrend(i) => Some((Direction::Bearish, strength)),
+                    _ => None,
+                }
+            }
+            Pattern::InvertedHammer | Pattern::ShootingStar => {
+                let shape = body > 0.0 && self.upper_shadow(i) >= 2.0 * body && self.lower_shadow(i) <= 0.1 * range;
+                let strength = self.upper_shadow(i) / range;
+                match pattern {
+                    Pattern::InvertedHammer if shape && self.in_downtrend(i) => Some((Direction::Bullish, strength)),
+                    Pattern::ShootingStar if shape && self.in_uptrend(i) => Some((Direction::Bearish, strength)),
+                    _ => None,
+                }
+            }
+            Pattern::Marubozu => {
+                if body >= 0.95 * range && self.is_long_body(i) {
+                    let direction = if self.is_bullish(i) { Direction::Bullish } else { Direction::Bearish };
+                    Some((direction, body / range))
+                } else {
+                    None
+                }
+            }
+            Pattern::Engulfing => {
+                if i < 1 || body <= self.body(i - 1) {
+                    None
+                } else if self.is_bearish(i - 1) && self.is_bullish(i) && open[i] <= close[i - 1] && close[i] >= open[i - 1] {
+                    Some((Direction::Bullish, 1.0 - self.body(i - 1) / body))
+                } else if self.is_bullish(i - 1) && self.is_bearish(i) && open[i] >= close[i - 1] && close[i] <= open[i - 1] {
+                    Some((Direction::Bearish, 1.0 - self.body(i - 1) / body))
+                } else {
+                    None
+                }
+            }
+            Pattern::Harami => {
+                if i < 1 || !self.is_long_body(i - 1) || body >= self.body(i - 1) {
+                    None
+                } else {
+                    let top = open[i].max(close[i]);
+                    let bottom = open[i].min(close[i]);
+                    let strength = 1.0 - body / self.body(i - 1);
+                    if self.is_bearish(i - 1) && self.is_bullish(i) && top < open[i - 1] && bottom > close[i - 1] {
+                        Some((Direction::Bullish, strength))
+                    } else if self.is_bullish(i - 1) && self.is_bearish(i) && top < close[i - 1] && bottom > open[i - 1] {
+                        Some((Direction::Bearish, strength))
+                    } else {
+                        None
+                    }
+                }
+            }
+            Pattern::Piercing => {
+                let mid = if i >= 1 { self.body_mid(i - 1) } else { 0.0 };
+                if i >= 1
+                    && self.is_bearish(i - 1)
+                    && self.is_long_body(i - 1)
+                    && self.is_bullish(i)
+                    && open[i] <= close[i - 1]
+                    && close[i] > mid
+                    && close[i] < open[i - 1]
+                {
+                    Some((Direction::Bullish, (close[i] - mid) / (open[i - 1] - mid)))
+                } else {
+                    None
+                }
+            }
+            Pattern::DarkCloudCover => {
+                let mid = if i >= 1 { self.body_mid(i - 1) } else { 0.0 };
+                if i >= 1
+                    && self.is_bullish(i - 1)
+                    && self.is_long_body(i - 1)
+                    && self.is_bearish(i)
+                    && open[i] >= close[i - 1]
+                    && close[i] < mid
+                    && close[i] > open[i - 1]
+                {
+                    Some((Direction::Bearish, (mid - close[i]) / (mid - open[i - 1])))
+                } else {
+                    None
+                }
+            }
+            Pattern::MorningStar | Pattern::EveningStar => {
+                if i < 2 || !self.is_long_body(i - 2) || !self.is_small_body(i - 1) {
+                    None
+                } else {
+                    let star = self.body_mid(i - 1);
+                    let first_mid = self.body_mid(i - 2);
+                    let strength = (body / self.body(i - 2)).min(1.0);
+                    match pattern {
+                        Pattern::MorningStar
+                            if self.is_bearish(i - 2) && star < close[i - 2] && self.is_bullish(i) && close[i] > first_mid =>
+                        {
+                            Some((Direction::Bullish, strength))
+                        }
+                        Pattern::EveningStar
+                            if self.is_bullish(i - 2) && star > close[i - 2] && self.is_bearish(i) && close[i] < first_mid =>
+                        {
+                            Some((Direction::Bearish, strength))
+                        }
+                        _ => None,
+                    }
+                }
+            }
+            Pattern::ThreeWhiteSoldiers | Pattern::ThreeBlackCrows => {
+                if i < 2 {
+                    None
+                } else {
+                    let bars = [i - 2, i - 1, i];
+                    let bullish = pattern == Pattern::ThreeWhiteSoldiers;
+                    let mut matched = true;
+                    let mut shadow = 0.0;
+                    for (k, &j) in bars.iter().enumerate() {
+                        let colour = if bullish { self.is_bullish(j) } else { self.is_bearish(j) };
+                        let tail = if bullish { self.upper_shadow(j) } else { self.lower_shadow(j) };
+                        if !colour || self.body(j) <= 0.0 || tail > 0.3 * self.body(j) {
+                            matched = false;
+                            break;
+                        }
+                        shadow += tail / self.body(j);
+                        if k > 0 {
+                            let p = bars[k - 1];
+                            let opens_inside = open[j] >= open[p].min(close[p]) && open[j] <= open[p].max(close[p]);
+                            let progresses = if bullish { close[j] > close[p] } else { close[j] < close[p] };
+                            if !opens_inside || !progresses {
+                                matched = false;
+                                break;
+                            }
+                        }
+                    }
+                    if !matched {
+                        None
+                    } else if bullish {
+                        Some((Direction::Bullish, 1.0 - shadow / 0.9))
+                    } else {
+                        Some((Direction::Bearish, 1.0 - shadow / 0.9))
+                    }
+                }
+            }
+        };
+
+        found.map(|(direction, strength)| PatternSignal {
+            pattern,
+            direction,
+            strength: strength.clamp(0.0, 1.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ten one-point bars falling from 120 to 110 (or rising from 100 to 110), then `tail`
+    fn feed(downtrend: bool, tail: &[(f64, f64, f64, f64)]) -> Patterns {
+        let mut ohlcv = DataFeed::new();
+        let mut push = |o: f64, h: f64, l: f64, c: f64| {
+            ohlcv.Date.push(format!("2024-01-{:02}", ohlcv.Date.len() + 1));
+            ohlcv.Open.push(o);
+            ohlcv.High.push(h);
+            ohlcv.Low.push(l);
+            ohlcv.Close.push(c);
+            ohlcv.Adj_Close.push(c);
+            ohlcv.Volume.push(1000);
+        };
+        for k in 0..10 {
+            let (o, c) = if downtrend { (120.0 - k as f64, 119.0 - k as f64) } else { (100.0 + k as f64, 101.0 + k as f64) };
+            push(o, o.max(c) + 0.2, o.min(c) - 0.2, c);
+        }
+        for &(o, h, l, c) in tail {
+            push(o, h, l, c);
+        }
+        Patterns { ohlcv }
+    }
+
+    // The pattern's sign on every bar: 1 bullish or neutral, -1 bearish, 0 none
+    fn signs(patterns: &mut Patterns, pattern: Pattern) -> Vec<i32> {
+        patterns
+            .signal(pattern)
+            .unwrap()
+            .iter()
+            .map(|s| if *s > 0.0 { 1 } else if *s < 0.0 { -1 } else { 0 })
+            .collect()
+    }
+
+    fn assert_only(downtrend: bool, tail: &[(f64, f64, f64, f64)], pattern: Pattern, sign: i32) {
+        let mut patterns = feed(downtrend, tail);
+        let found = signs(&mut patterns, pattern);
+        let mut expected = vec![0; found.len()];
+        expected[found.len() - 1] = sign;
+        assert_eq!(found, expected, "{:?}", pattern);
+    }
+
+    #[test]
+    fn single_bar_patterns() {
+        assert_only(true, &[(109.5, 110.02, 108.0, 110.0)], Pattern::Hammer, 1);
+        assert_only(true, &[(109.5, 111.5, 109.48, 110.0)], Pattern::InvertedHammer, 1);
+        assert_only(false, &[(110.5, 111.02, 109.0, 111.0)], Pattern::HangingMan, -1);
+        assert_only(false, &[(110.5, 112.5, 110.48, 111.0)], Pattern::ShootingStar, -1);
+        assert_only(true, &[(110.0, 114.05, 109.95, 114.0)], Pattern::Marubozu, 1);
+        assert_only(true, &[(110.0, 111.0, 109.0, 110.02)], Pattern::Doji, 1);
+    }
+
+    #[test]
+    fn reversal_shapes_need_the_trend() {
+        // a hammer shape after a rally is a hanging man, not a hammer
+        let mut patterns = feed(false, &[(110.5, 111.02, 109.0, 111.0)]);
+        assert!(signs(&mut patterns, Pattern::Hammer).iter().all(|s| *s == 0));
+        let mut patterns = feed(true, &[(109.5, 110.02, 108.0, 110.0)]);
+        assert!(signs(&mut patterns, Pattern::HangingMan).iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn two_bar_patterns() {
+        assert_only(true, &[(109.8, 111.6, 109.7, 111.5)], Pattern::Engulfing, 1);
+        assert_only(false, &[(111.2, 111.3, 108.4, 108.5)], Pattern::Engulfing, -1);
+        assert_only(true, &[(110.0, 110.1, 105.9, 106.0), (107.0, 108.2, 106.8, 108.0)], Pattern::Harami, 1);
+        assert_only(false, &[(110.0, 114.1, 109.9, 114.0), (113.0, 113.2, 111.8, 112.0)], Pattern::Harami, -1);
+        assert_only(true, &[(110.0, 110.1, 105.9, 106.0), (105.5, 108.6, 105.4, 108.5)], Pattern::Piercing, 1);
+        assert_only(false, &[(110.0, 114.1, 109.9, 114.0), (114.5, 114.6, 111.4, 111.5)], Pattern::DarkCloudCover, -1);
+    }
+
+    #[test]
+    fn three_bar_patterns() {
+        assert_only(
+            true,
+            &[(110.0, 110.1, 105.9, 106.0), (105.2, 105.6, 105.0, 105.4), (105.6, 109.1, 105.5, 109.0)],
+            Pattern::MorningStar,
+            1,
+        );
+        assert_only(
+            false,
+            &[(110.0, 114.1, 109.9, 114.0), (114.6, 115.0, 114.4, 114.8), (114.4, 114.5, 110.9, 111.0)],
+            Pattern::EveningStar,
+            -1,
+        );
+        assert_only(
+            true,
+            &[(110.0, 112.2, 109.9, 112.0), (111.0, 113.2, 110.9, 113.0), (112.0, 114.3, 111.9, 114.0)],
+            Pattern::ThreeWhiteSoldiers,
+            1,
+        );
+        assert_only(
+            false,
+            &[(112.0, 112.1, 109.9, 110.0), (111.0, 111.1, 108.9, 109.0), (110.0, 110.1, 107.9, 108.0)],
+            Pattern::ThreeBlackCrows,
+            -1,
+        );
+    }
+
+    #[test]
+    fn scan_reports_every_pattern_on_the_bar() {
+        let mut patterns = feed(true, &[(109.8, 111.6, 109.7, 111.5)]);
+        let signals = patterns.scan().unwrap();
+        let last = &signals[signals.len() - 1];
+        assert!(last.iter().any(|s| s.pattern == Pattern::Engulfing && s.direction == Direction::Bullish));
+        assert!(last.iter().all(|s| (0.0..=1.0).contains(&s.strength)));
+    }
+}
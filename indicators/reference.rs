@@ -0,0 +1,203 @@
+use crate::data_reader::data::data::DataFeed;
+use crate::indicators::indicators::{Indicators, MaType, VolEstimator};
+use csv::Reader;
+use std::collections::HashMap;
+
+// Checks every indicator against the reference values in golden.csv in `fixture_dir`,
+// which make_golden.py computes from ohlcv.csv with its own Python implementations.
+// Blank golden cells are warm-up bars and are skipped.
+pub fn verify(fixture_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    verify_against(fixture_dir, "golden.csv", true)
+}
+
+// Checks the indicators TA-Lib also has against talib.csv, written from TA-Lib itself
+// by `make_golden.py --talib`. Where TA-Lib has no value the file is blank and the bar is
+// skipped, which covers the deliberate differences:
+// - warm-up bars are 0.0 here instead of NaN
+// - KAMA also outputs its seed, the close of bar period - 1, one bar before TA-Lib starts
+// - BBANDS is the close's position in the bands, (close - middle) / (upper - middle), and
+//   0.0 on a flat window where TA-Lib's bands collapse and the ratio is undefined
+pub fn verify_talib(fixture_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    verify_against(fixture_dir, "talib.csv", false)
+}
+
+// With `complete`, every indicator output must have a column in `golden_file`;
+// otherwise only the outputs the file has are checked
+fn verify_against(fixture_dir: &str, golden_file: &str, complete: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut data_feed = DataFeed::new();
+    data_feed.read_csv(&format!("{}/ohlcv.csv", fixture_dir))?;
+    let golden = read_golden(&format!("{}/{}", fixture_dir, golden_file))?;
+    let mut ind = Indicators { ohlcv: data_feed };
+
+    let mut actual: Vec<(&str, Vec<f64>)> = vec![
+        ("SMA_10", ind.SMA(10)?),
+        ("EMA_10", ind.EMA(10)?),
+        ("WMA_10", ind.WMA(10)?),
+        ("DEMA_10", ind.DEMA(10)?),
+        ("TEMA_10", ind.TEMA(10)?),
+        ("KAMA_10", ind.KAMA(10)?),
+        ("HMA_10", ind.HMA(10)?),
+        ("ZLEMA_10", ind.ZLEMA(10)?),
+        ("VWMA_10", ind.VWMA(10)?),
+    ];
+    let (macd, signal) = ind.MACD(9, 12, 26, MaType::EMA)?;
+    actual.push(("MACD_12_26_9", macd));
+    actual.push(("MACD_SIGNAL_12_26_9", signal));
+    actual.push(("ATR_14", ind.ATR(14)?));
+    actual.push(("BBANDS_20", ind.BBANDS(20, MaType::SMA)?));
+    actual.push(("STOCHASTIC_14", ind.STOCHASTIC(14)?));
+    actual.push(("WILLIAMS_14", ind.WILLIAMS(14)?));
+    let (adx, plus_di, minus_di) = ind.ADX(14)?;
+    actual.push(("ADX_14", adx));
+    actual.push(("PLUS_DI_14", plus_di));
+    actual.push(("MINUS_DI_14", minus_di));
+    actual.push(("PSAR_0.02_0.2", ind.PSAR(0.02, 0.2)?));
+    let (aroon_up, aroon_down) = ind.AROON(14)?;
+    actual.push(("AROON_UP_14", aroon_up));
+    actual.push(("AROON_DOWN_14", aroon_down));
+    actual.push(("OBV", ind.OBV()?));
+    actual.push(("AD", ind.AD()?));
+    actual.push(("CHAIKIN_3_10", ind.CHAIKIN(3, 10)?));
+    actual.push(("CMF_20", ind.CMF(20)?));
+    actual.push(("VWAP", ind.VWAP()?));
+    actual.push(("AVWAP_30", ind.AVWAP(30)?));
+    // the same anchor, found by its date
+    let anchor = ind.ohlcv.Date.get(30).cloned().ok_or("Not enough bars for AVWAP_30")?;
+    actual.push(("AVWAP_30", ind.AVWAP_DATE(&anchor)?));
+    let (supertrend, direction) = ind.SUPERTREND(10, 3.0)?;
+    actual.push(("SUPERTREND_10_3", supertrend));
+    actual.push(("SUPERTREND_DIR_10_3", direction));
+    let (tenkan, kijun, senkou_a, senkou_b, chikou) = ind.ICHIMOKU(9, 26, 52)?;
+    actual.push(("TENKAN_9", tenkan));
+    actual.push(("KIJUN_26", kijun));
+    actual.push(("SENKOU_A_9_26", senkou_a));
+    actual.push(("SENKOU_B_52_26", senkou_b));
+    actual.push(("CHIKOU_26", chikou));
+    let (upper, middle, lower) = ind.KELTNER(20, 10, 2.0)?;
+    actual.push(("KELTNER_UP_20_10_2", upper));
+    actual.push(("KELTNER_MID_20_10_2", middle));
+    actual.push(("KELTNER_LOW_20_10_2", lower));
+    let (upper, middle, lower) = ind.DONCHIAN(20)?;
+    actual.push(("DONCHIAN_UP_20", upper));
+    actual.push(("DONCHIAN_MID_20", middle));
+    actual.push(("DONCHIAN_LOW_20", lower));
+    actual.push(("HV_CC_20", ind.HV(20, VolEstimator::CloseToClose, 252.0)?));
+    actual.push(("HV_PARKINSON_20", ind.HV(20, VolEstimator::Parkinson, 252.0)?));
+    actual.push(("HV_GK_20", ind.HV(20, VolEstimator::GarmanKlass, 252.0)?));
+    actual.push(("HV_YZ_20", ind.HV(20, VolEstimator::YangZhang, 252.0)?));
+    let (long_exit, short_exit) = ind.CHANDELIER(22, 3.0)?;
+    actual.push(("CHANDELIER_LONG_22_3", long_exit));
+    actual.push(("CHANDELIER_SHORT_22_3", short_exit));
+
+    let mut failures = Vec::new();
+    for (name, series) in &actual {
+        let expected = match golden.get(*name) {
+            Some(expected) => expected,
+            None if complete => {
+                failures.push(format!("{}: missing from golden file", name));
+                continue;
+            }
+            None => continue,
+        };
+        match first_mismatch(series, expected) {
+            Some(i) => failures.push(format!(
+                "{}: bar {} expected {} got {}",
+                name, i, expected[i].unwrap_or(f64::NAN), series[i]
+            )),
+            None => println!("ok {}", name),
+        }
+    }
+    for name in golden.keys() {
+        if !actual.iter().any(|(n, _)| n == name) {
+            failures.push(format!("{}: golden column is not checked", name));
+        }
+    }
+
+    if failures.is_empty() {
+        println!("all {} indicator outputs match {}", golden.len(), golden_file);
+        Ok(())
+    } else {
+        Err(failures.join("\n").into())
+    }
+}
+
+// Column name to its values, None for blank cells
+type Golden = HashMap<String, Vec<Option<f64>>>;
+
+fn read_golden(filename: &str) -> Result<Golden, Box<dyn std::error::Error>> {
+    let mut reader = Reader::from_path(filename)?;
+    let names: Vec<String> = reader.headers()?.iter().map(|s| s.to_string()).collect();
+    let mut golden: Golden = names.iter().map(|n| (n.clone(), Vec::new())).collect();
+
+    for line in reader.records() {
+        let line = line?;
+        for (name, cell) in names.iter().zip(line.iter()) {
+            let value = if cell.is_empty() { None } else { Some(cell.parse::<f64>()?) };
+            golden.get_mut(name).ok_or("Malformed golden file")?.push(value);
+        }
+    }
+    Ok(golden)
+}
+
+fn first_mismatch(actual: &[f64], expected: &[Option<f64>]) -> Option<usize> {
+    if actual.len() != expected.len() {
+        return Some(actual.len().min(expected.len()));
+    }
+    for (i, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+        if let Some(e) = e {
+            if (a - e).abs() > 1e-8 * e.abs().max(1.0) {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/indicators/fixtures");
+
+    #[test]
+    fn indicators_match_golden_values() {
+        verify(FIXTURES).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs talib.csv from `make_golden.py --talib` on a machine with TA-Lib"]
+    fn indicators_match_talib() {
+        verify_talib(FIXTURES).unwrap();
+    }
+
+    fn closes(values: &[f64]) -> Indicators {
+        let mut ohlcv = DataFeed::new();
+        for (k, &c) in values.iter().enumerate() {
+            ohlcv.Date.push(format!("2024-01-{:02}", k + 1));
+            ohlcv.Open.push(c);
+            ohlcv.High.push(c);
+            ohlcv.Low.push(c);
+            ohlcv.Close.push(c);
+            ohlcv.Adj_Close.push(c);
+            ohlcv.Volume.push(1000);
+        }
+        Indicators { ohlcv }
+    }
+
+    #[test]
+    fn kama_outputs_its_seed_close_before_the_first_ratio() {
+        let kama = closes(&[10.0, 11.0, 13.0, 12.0, 15.0]).KAMA(3).unwrap();
+        assert_eq!(&kama[..3], &[0.0, 0.0, 13.0]);
+        // efficiency ratios 2 / 4 and 4 / 6 between the 2- and 30-bar constants
+        assert!((kama[3] - 12.866342929818476).abs() < 1e-12);
+        assert!((kama[4] - 13.32957958512953).abs() < 1e-12);
+    }
+
+    #[test]
+    fn bbands_position_is_zero_on_a_flat_window() {
+        let bbands = closes(&[10.0, 10.0, 10.0, 10.0, 12.0]).BBANDS(4, MaType::SMA).unwrap();
+        assert_eq!(&bbands[..4], &[0.0; 4]);
+        // window 10, 10, 10, 12: middle 10.5, population deviation sqrt(0.75)
+        assert!((bbands[4] - 1.5 / (2.0 * 0.75f64.sqrt())).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,630 @@
+use crate::data_reader::data::data::DataFeed;
+
+#[derive(Debug, Clone)]
+pub struct OlsFit {
+    pub alpha: f64,
+    pub beta: f64,
+    pub residuals: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdfResult {
+    pub statistic: f64,
+    pub p_value: f64,
+    pub lags: usize,
+    pub observations: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CointResult {
+    // y = alpha + beta * x + spread
+    pub alpha: f64,
+    pub beta: f64,
+    pub statistic: f64,
+    pub p_value: f64,
+    pub spread: Vec<f64>,
+}
+
+// Two feeds on the same dates, e.g. the legs of a pair. `y` is regressed on `x`.
+pub struct PairStats {
+    pub x: DataFeed,
+    pub y: DataFeed,
+}
+
+impl PairStats {
+    pub fn new(x: DataFeed, y: DataFeed) -> Result<Self, Box<dyn std::error::Error>> {
+        if x.Date != y.Date {
+            return Err("Feeds are not aligned on the same dates".into());
+        }
+        if x.Close.len() < 3 {
+            return Err("Not enough data".into());
+        }
+        Ok(PairStats { x, y })
+    }
+
+    // Rolling correlation of the two feeds' log returns
+    pub fn CORRELATION(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        rolling_correlation(&log_returns(&self.x.Close), &log_returns(&self.y.Close), period as usize, 1)
+    }
+
+    // Rolling OLS hedge ratio of y's close on x's close
+    pub fn BETA(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let (_, beta) = rolling_ols(&self.y.Close, &self.x.Close, period as usize)?;
+        Ok(beta)
+    }
+
+    // Rolling z-score of the spread y - beta * x with the rolling hedge ratio
+    pub fn SPREAD_ZSCORE(&mut self, period: i64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+        let p = period as usize;
+        let (alpha, beta) = rolling_ols(&self.y.Close, &self.x.Close, p)?;
+        let len = self.x.Close.len();
+        let mut spread = vec![0.0; len];
+        for i in (p - 1)..len {
+            spread[i] = self.y.Close[i] - alpha[i] - beta[i] * self.x.Close[i];
+        }
+        zscore_from(&spread, p, p - 1)
+    }
+
+    pub fn COINTEGRATION(&mut self, lags: usize) -> Result<CointResult, Box<dyn std::error::Error>> {
+        engle_granger(&self.y.Close, &self.x.Close, lags)
+    }
+
+    // Half-life in bars of the static Engle-Granger spread
+    pub fn HALF_LIFE(&mut self) -> Result<f64, Box<dyn std::error::Error>> {
+        let fit = ols(&self.y.Close, &self.x.Close)?;
+        half_life(&fit.residuals)
+    }
+
+    pub fn HURST(&mut self, max_lag: usize) -> Result<f64, Box<dyn std::error::Error>> {
+        let fit = ols(&self.y.Close, &self.x.Close)?;
+        hurst(&fit.residuals, max_lag)
+    }
+}
+
+pub fn log_returns(data: &[f64]) -> Vec<f64> {
+    let mut returns = vec![0.0; data.len()];
+    for i in 1..data.len() {
+        if data[i] > 0.0 && data[i - 1] > 0.0 {
+            returns[i] = (data[i] / data[i - 1]).ln();
+        }
+    }
+    returns
+}
+
+fn mean(data: &[f64]) -> f64 {
+    data.iter().sum::<f64>() / data.len() as f64
+}
+
+fn sample_std(data: &[f64]) -> f64 {
+    let m = mean(data);
+    (data.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (data.len() as f64 - 1.0)).sqrt()
+}
+
+// Annualised Sharpe ratio of per-bar returns, zero risk-free rate
+pub fn sharpe_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
+    if returns.len() < 2 {
+        return 0.0;
+    }
+    let std = sample_std(returns);
+    if std <= 0.0 {
+        return 0.0;
+    }
+    mean(returns) / std * periods_per_year.sqrt()
+}
+
+// Population skewness and (non-excess) kurtosis
+pub fn moments(data: &[f64]) -> (f64, f64) {
+    let m = mean(data);
+    let var = data.iter().map(|v| (v - m).powi(2)).sum::<f64>() / data.len() as f64;
+    if var <= 0.0 {
+        return (0.0, 3.0);
+    }
+    let skew = data.iter().map(|v| (v - m).powi(3)).sum::<f64>() / data.len() as f64 / var.powf(1.5);
+    let kurt = data.iter().map(|v| (v - m).powi(4)).sum::<f64>() / data.len() as f64 / (var * var);
+    (skew, kurt)
+}
+
+// Probability that the true per-bar Sharpe ratio exceeds `benchmark` (also per bar), given
+// the sample length, skewness and kurtosis of `returns` (Bailey and Lopez de Prado, 2012)
+pub fn probabilistic_sharpe(returns: &[f64], benchmark: f64) -> f64 {
+    if returns.len() < 3 {
+        return 0.0;
+    }
+    let sr = sharpe_ratio(returns, 1.0);
+    let (skew, kurt) = moments(returns);
+    let denom = 1.0 - skew * sr + (kurt - 1.0) / 4.0 * sr * sr;
+    if denom <= 0.0 {
+        return 0.0;
+    }
+    normal_cdf((sr - benchmark) * (returns.len() as f64 - 1.0).sqrt() / denom.sqrt())
+}
+
+// Expected maximum per-bar Sharpe ratio of `trial_sharpes.len()` unskilled trials with the
+// same variance as the trials run
+pub fn expected_max_sharpe(trial_sharpes: &[f64]) -> f64 {
+    let n = trial_sharpes.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let euler = 0.5772156649015329;
+    let std = sample_std(trial_sharpes);
+    std * ((1.0 - euler) * normal_ppf(1.0 - 1.0 / n as f64)
+        + euler * normal_ppf(1.0 - 1.0 / (n as f64 * std::f64::consts::E)))
+}
+
+// PSR of the chosen strategy against the Sharpe ratio expected from luck alone after
+// `trial_sharpes.len()` trials (Bailey and Lopez de Prado, 2014)
+pub fn deflated_sharpe(returns: &[f64], trial_sharpes: &[f64]) -> f64 {
+    probabilistic_sharpe(returns, expected_max_sharpe(trial_sharpes))
+}
+
+// Probability of backtest overfitting by combinatorially symmetric cross-validation.
+// `trials` holds the per-bar returns of every configuration over the same bars; they are
+// cut into `partitions` blocks and every half of the blocks is used once as in-sample.
+// Returns the share of splits where the in-sample winner ranks at or below the
+// out-of-sample median (Bailey, Borwein, Lopez de Prado and Zhu, 2015).
+pub fn pbo(trials: &[Vec<f64>], partitions: usize) -> Result<f64, Box<dyn std::error::Error>> {
+    if trials.len() < 2 {
+        return Err("Need at least two trials".into());
+    }
+    if partitions < 2 || partitions % 2 == 1 {
+        return Err("Partitions must be an even number of at least two".into());
+    }
+    let len = trials[0].len();
+    if trials.iter().any(|t| t.len() != len) {
+        return Err("Trials must cover the same bars".into());
+    }
+    if len < partitions * 2 {
+        return Err("Not enough bars for the number of partitions".into());
+    }
+    let bounds: Vec<(usize, usize)> = (0..partitions)
+        .map(|p| (p * len / partitions, (p + 1) * len / partitions))
+        .collect();
+
+    // per trial and block: (sum, sum of squares), so each split's Sharpe ratios come from
+    // block totals instead of another pass over the bars
+    let sums: Vec<Vec<(f64, f64)>> = trials
+        .iter()
+        .map(|t| bounds.iter().map(|(s, e)| t[*s..*e].iter().fold((0.0, 0.0), |acc, r| (acc.0 + r, acc.1 + r * r))).collect())
+        .collect();
+    let block_sharpe = |trial: usize, blocks: &[usize]| {
+        let (mut sum, mut sq, mut n) = (0.0, 0.0, 0.0);
+        for &p in blocks {
+            sum += sums[trial][p].0;
+            sq += sums[trial][p].1;
+            n += (bounds[p].1 - bounds[p].0) as f64;
+        }
+        let var = (sq - sum * sum / n) / (n - 1.0);
+        if var <= 0.0 {
+            0.0
+        } else {
+            sum / n / var.sqrt()
+        }
+    };
+
+    let mut overfit = 0;
+    let splits = combinations(partitions, partitions / 2);
+    for in_sample in &splits {
+        let out_sample: Vec<usize> = (0..partitions).filter(|p| !in_sample.contains(p)).collect();
+        let is_sharpe: Vec<f64> = (0..trials.len()).map(|t| block_sharpe(t, in_sample)).collect();
+        let oos_sharpe: Vec<f64> = (0..trials.len()).map(|t| block_sharpe(t, &out_sample)).collect();
+        let best = (0..trials.len())
+            .max_by(|a, b| is_sharpe[*a].partial_cmp(&is_sharpe[*b]).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap();
+        let rank = oos_sharpe.iter().filter(|s| **s <= oos_sharpe[best]).count();
+        let omega = rank as f64 / (trials.len() as f64 + 1.0);
+        if (omega / (1.0 - omega)).ln() <= 0.0 {
+            overfit += 1;
+        }
+    }
+    Ok(overfit as f64 / splits.len() as f64)
+}
+
+// Linearly interpolated quantile of already sorted data
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+// All k-element subsets of 0..n in lexicographic order
+pub fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut out = Vec::new();
+    if k > n {
+        return out;
+    }
+    let mut current: Vec<usize> = (0..k).collect();
+    loop {
+        out.push(current.clone());
+        let mut i = k;
+        while i > 0 && current[i - 1] == n - k + i - 1 {
+            i -= 1;
+        }
+        if i == 0 {
+            return out;
+        }
+        current[i - 1] += 1;
+        for j in i..k {
+            current[j] = current[j - 1] + 1;
+        }
+    }
+}
+
+// Rolling z-score using the sample standard deviation; 0.0 during warm-up
+pub fn zscore(data: &[f64], period: usize) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    zscore_from(data, period, 0)
+}
+
+// Rolling z-score of data[offset..], for series with a warm-up of their own
+pub fn zscore_from(data: &[f64], period: usize, offset: usize) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    if period < 2 || offset + period > data.len() {
+        return Err("Period exceeds data length".into());
+    }
+    let mut z = vec![0.0; data.len()];
+    for i in (offset + period - 1)..data.len() {
+        let window = &data[(i + 1 - period)..=i];
+        let sd = sample_std(window);
+        z[i] = if sd > 0.0 { (data[i] - mean(window)) / sd } else { 0.0 };
+    }
+    Ok(z)
+}
+
+pub fn rolling_correlation(x: &[f64], y: &[f64], period: usize, offset: usize) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    if x.len() != y.len() {
+        return Err("Series are not aligned".into());
+    }
+    if period < 2 || offset + period > x.len() {
+        return Err("Period exceeds data length".into());
+    }
+    let mut corr = vec![0.0; x.len()];
+    for i in (offset + period - 1)..x.len() {
+        let xs = &x[(i + 1 - period)..=i];
+        let ys = &y[(i + 1 - period)..=i];
+        let (mx, my) = (mean(xs), mean(ys));
+        let mut cov = 0.0;
+        let mut vx = 0.0;
+        let mut vy = 0.0;
+        for (a, b) in xs.iter().zip(ys.iter()) {
+            cov += (a - mx) * (b - my);
+            vx += (a - mx).powi(2);
+            vy += (b - my).powi(2);
+        }
+        corr[i] = if vx > 0.0 && vy > 0.0 { cov / (vx * vy).sqrt() } else { 0.0 };
+    }
+    Ok(corr)
+}
+
+// Rolling (alpha, beta) of y = alpha + beta * x over `period` bars
+pub fn rolling_ols(y: &[f64], x: &[f64], period: usize) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+    if x.len() != y.len() {
+        return Err("Series are not aligned".into());
+    }
+    if period < 2 || period > x.len() {
+        return Err("Period exceeds data length".into());
+    }
+    let mut alpha = vec![0.0; x.len()];
+    let mut beta = vec![0.0; x.len()];
+    for i in (period - 1)..x.len() {
+        let fit = ols(&y[(i + 1 - period)..=i], &x[(i + 1 - period)..=i])?;
+        alpha[i] = fit.alpha;
+        beta[i] = fit.beta;
+    }
+    Ok((alpha, beta))
+}
+
+pub fn ols(y: &[f64], x: &[f64]) -> Result<OlsFit, Box<dyn std::error::Error>> {
+    if x.len() != y.len() || x.len() < 2 {
+        return Err("Series are not aligned".into());
+    }
+    let (mx, my) = (mean(x), mean(y));
+    let mut sxy = 0.0;
+    let mut sxx = 0.0;
+    for (a, b) in x.iter().zip(y.iter()) {
+        sxy += (a - mx) * (b - my);
+        sxx += (a - mx).powi(2);
+    }
+    if sxx == 0.0 {
+        return Err("Regressor has no variance".into());
+    }
+    let beta = sxy / sxx;
+    let alpha = my - beta * mx;
+    let residuals = x.iter().zip(y.iter()).map(|(a, b)| b - alpha - beta * a).collect();
+    Ok(OlsFit { alpha, beta, residuals })
+}
+
+// Multiple regression of y on the columns of x. Returns (coefficients, standard errors).
+fn ols_multi(y: &[f64], x: &[Vec<f64>]) -> Result<(Vec<f64>, Vec<f64>), Box<dyn std::error::Error>> {
+    let n = y.len();
+    let k = x.len();
+    if n <= k {
+        return Err("Not enough observations for regression".into());
+    }
+    let mut xtx = vec![vec![0.0; k]; k];
+    let mut xty = vec![0.0; k];
+    for a in 0..k {
+        for b in 0..k {
+            xtx[a][b] = (0..n).map(|t| x[a][t] * x[b][t]).sum();
+        }
+        xty[a] = (0..n).map(|t| x[a][t] * y[t]).sum();
+    }
+    let inv = invert(xtx)?;
+    let coef: Vec<f64> = (0..k).map(|a| (0..k).map(|b| inv[a][b] * xty[b]).sum()).collect();
+    let rss: f64 = (0..n)
+        .map(|t| {
+            let fitted: f64 = (0..k).map(|a| coef[a] * x[a][t]).sum();
+            (y[t] - fitted).powi(2)
+        })
+        .sum();
+    let sigma2 = rss / (n - k) as f64;
+    let se = (0..k).map(|a| (sigma2 * inv[a][a]).sqrt()).collect();
+    Ok((coef, se))
+}
+
+// Gauss-Jordan inverse with partial pivoting
+fn invert(mut m: Vec<Vec<f64>>) -> Result<Vec<Vec<f64>>, Box<dyn std::error::Error>> {
+    let k = m.len();
+    let mut inv: Vec<Vec<f64>> = (0..k).map(|i| (0..k).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+    for col in 0..k {
+        let pivot = (col..k)
+            .max_by(|a, b| m[*a][col].abs().partial_cmp(&m[*b][col].abs()).unwrap_or(std::cmp::Ordering::Equal))
+            .ok_or("Singular matrix")?;
+        if m[pivot][col].abs() < 1e-12 {
+            return Err("Singular matrix".into());
+        }
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+        let d = m[col][col];
+        for j in 0..k {
+            m[col][j] /= d;
+            inv[col][j] /= d;
+        }
+        for row in 0..k {
+            if row != col {
+                let f = m[row][col];
+                for j in 0..k {
+                    m[row][j] -= f * m[col][j];
+                    inv[row][j] -= f * inv[col][j];
+                }
+            }
+        }
+    }
+    Ok(inv)
+}
+
+// Augmented Dickey-Fuller test of `data` for a unit root. With `constant` the test
+// regression includes an intercept; p-values use MacKinnon's (1994) surface for one series
+// with or without a constant to match.
+pub fn adf(data: &[f64], lags: usize, constant: bool) -> Result<AdfResult, Box<dyn std::error::Error>> {
+    let (statistic, observations) = adf_statistic(data, lags, constant)?;
+    Ok(AdfResult {
+        statistic,
+        p_value: mackinnon_p(statistic, 1, constant),
+        lags,
+        observations,
+    })
+}
+
+fn adf_statistic(data: &[f64], lags: usize, constant: bool) -> Result<(f64, usize), Box<dyn std::error::Error>> {
+    let n = data.len();
+    if n < lags + 10 {
+        return Err("Not enough data for ADF test".into());
+    }
+    let diff: Vec<f64> = (1..n).map(|i| data[i] - data[i - 1]).collect();
+    // diff[t - 1] is data[t] - data[t - 1]
+    let first = lags + 1;
+    let y: Vec<f64> = (first..n).map(|t| diff[t - 1]).collect();
+    let mut x: Vec<Vec<f64>> = vec![(first..n).map(|t| data[t - 1]).collect()];
+    for lag in 1..=lags {
+        x.push((first..n).map(|t| diff[t - 1 - lag]).collect());
+    }
+    if constant {
+        x.push(vec![1.0; y.len()]);
+    }
+    let (coef, se) = ols_multi(&y, &x)?;
+    if se[0] == 0.0 {
+        return Err("Degenerate ADF regression".into());
+    }
+    Ok((coef[0] / se[0], y.len()))
+}
+
+// Engle-Granger two-step test: OLS of y on x, then ADF without intercept on the residuals.
+// The p-value uses MacKinnon's surface for two variables with a constant, since the
+// cointegrating regression has one.
+pub fn engle_granger(y: &[f64], x: &[f64], lags: usize) -> Result<CointResult, Box<dyn std::error::Error>> {
+    let fit = ols(y, x)?;
+    let (statistic, _) = adf_statistic(&fit.residuals, lags, false)?;
+    Ok(CointResult {
+        alpha: fit.alpha,
+        beta: fit.beta,
+        statistic,
+        p_value: mackinnon_p(statistic, 2, true),
+        spread: fit.residuals,
+    })
+}
+
+// Half-life in bars from the AR(1) fit ds_t = a + lambda * s_{t-1}; infinite if not mean reverting
+pub fn half_life(spread: &[f64]) -> Result<f64, Box<dyn std::error::Error>> {
+    if spread.len() < 3 {
+        return Err("Not enough data".into());
+    }
+    let delta: Vec<f64> = (1..spread.len()).map(|i| spread[i] - spread[i - 1]).collect();
+    let fit = ols(&delta, &spread[..spread.len() - 1])?;
+    if fit.beta >= 0.0 {
+        return Ok(f64::INFINITY);
+    }
+    Ok(-std::f64::consts::LN_2 / fit.beta)
+}
+
+// Hurst exponent from the scaling of lagged differences: std(x[t + lag] - x[t]) ~ lag^H.
+// Below 0.5 the series mean reverts, above 0.5 it trends.
+pub fn hurst(data: &[f64], max_lag: usize) -> Result<f64, Box<dyn std::error::Error>> {
+    if max_lag < 3 || max_lag * 2 >= data.len() {
+        return Err("max_lag must be at least 3 and under half the data length".into());
+    }
+    let mut log_lag = Vec::new();
+    let mut log_tau = Vec::new();
+    for lag in 2..=max_lag {
+        let diffs: Vec<f64> = (lag..data.len()).map(|t| data[t] - data[t - lag]).collect();
+        let tau = sample_std(&diffs);
+        if tau > 0.0 {
+            log_lag.push((lag as f64).ln());
+            log_tau.push(tau.ln());
+        }
+    }
+    Ok(ols(&log_tau, &log_lag)?.beta)
+}
+
+// MacKinnon (1994) approximate p-value for a unit-root statistic, for `n` = 1 (ADF) or
+// 2 (two-variable cointegration), from the regression with (`constant`) or without an intercept
+pub fn mackinnon_p(statistic: f64, n: usize, constant: bool) -> f64 {
+    // ['c' surface, 'nc' surface][n - 1]
+    const TAU_MAX: [[f64; 2]; 2] = [[2.74, 0.92], [1.51, 0.86]];
+    const TAU_MIN: [[f64; 2]; 2] = [[-18.83, -18.86], [-19.04, -19.62]];
+    const TAU_STAR: [[f64; 2]; 2] = [[-1.61, -2.62], [-1.04, -1.53]];
+    const SMALL_P: [[[f64; 3]; 2]; 2] = [
+        [[2.1659, 1.4412, 0.038269], [2.92, 1.5012, 0.039796]],
+        [[0.6344, 1.2378, 0.032496], [1.9129, 1.3857, 0.035322]],
+    ];
+    const LARGE_P: [[[f64; 4]; 2]; 2] = [
+        [[1.7339, 0.93202, -0.12745, -0.010368], [2.1945, 0.64695, -0.29198, -0.042377]],
+        [[0.4797, 0.93557, -0.06999, 0.033066], [1.5578, 0.8558, -0.2083, -0.0033549]],
+    ];
+
+    let t = if constant { 0 } else { 1 };
+    let k = n.clamp(1, 2) - 1;
+    if statistic > TAU_MAX[t][k] {
+        return 1.0;
+    }
+    if statistic < TAU_MIN[t][k] {
+        return 0.0;
+    }
+    let z = if statistic <= TAU_STAR[t][k] {
+        SMALL_P[t][k].iter().enumerate().map(|(i, c)| c * statistic.powi(i as i32)).sum::<f64>()
+    } else {
+        LARGE_P[t][k].iter().enumerate().map(|(i, c)| c * statistic.powi(i as i32)).sum::<f64>()
+    };
+    normal_cdf(z)
+}
+
+pub fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / std::f64::consts::SQRT_2))
+}
+
+// Inverse of normal_cdf (Acklam's rational approximation, relative error below 1.2e-9)
+pub fn normal_ppf(p: f64) -> f64 {
+    const A: [f64; 6] = [-3.969683028665376e+01, 2.209460984245205e+02, -2.759285104469687e+02, 1.38357751867269e+02, -3.066479806614716e+01, 2.506628277459239e+00];
+    const B: [f64; 5] = [-5.447609879822406e+01, 1.615858368580409e+02, -1.556989798598866e+02, 6.680131188771972e+01, -1.328068155288572e+01];
+    const C: [f64; 6] = [-7.784894002430293e-03, -3.223964580411365e-01, -2.400758277161838e+00, -2.549732539343734e+00, 4.374664141464968e+00, 2.938163982698783e+00];
+    const D: [f64; 4] = [7.784695709041462e-03, 3.224671290700398e-01, 2.445134137142996e+00, 3.754408661907416e+00];
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+    let tail = |q: f64| (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5]) / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0);
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+// Abramowitz and Stegun 7.1.26, absolute error below 1.5e-7
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mackinnon_p_matches_the_five_percent_critical_values() {
+        // asymptotic 5% critical values: -2.86 with a constant, -1.94 without, -3.34 for two series
+        assert!((mackinnon_p(-2.86, 1, true) - 0.05).abs() < 0.003);
+        assert!((mackinnon_p(-1.94, 1, false) - 0.05).abs() < 0.003);
+        assert!((mackinnon_p(-3.34, 2, true) - 0.05).abs() < 0.003);
+        // the same statistic is far less significant on the 'c' surface
+        assert!(mackinnon_p(-1.94, 1, true) > 0.3);
+    }
+
+    // a positive per-bar Sharpe ratio over 200 bars
+    fn returns() -> Vec<f64> {
+        (0..200).map(|i| 0.002 + 0.01 * ((i * 7 % 11) as f64 - 5.0) / 5.0).collect()
+    }
+
+    #[test]
+    fn probabilistic_sharpe_is_one_half_at_the_benchmark() {
+        let r = returns();
+        let sr = sharpe_ratio(&r, 1.0);
+        assert!((probabilistic_sharpe(&r, sr) - 0.5).abs() < 1e-6);
+        assert!(probabilistic_sharpe(&r, 0.0) > 0.5);
+        assert!(probabilistic_sharpe(&r, 2.0 * sr) < 0.5);
+        // more bars with the same Sharpe ratio are more conclusive
+        assert!(probabilistic_sharpe(&r.repeat(4), 0.0) > probabilistic_sharpe(&r, 0.0));
+    }
+
+    #[test]
+    fn expected_max_sharpe_grows_with_the_spread_of_the_trials() {
+        assert_eq!(expected_max_sharpe(&[0.3]), 0.0);
+        let trials: Vec<f64> = (0..100).map(|i| i as f64 / 100.0).collect();
+        let emax = expected_max_sharpe(&trials);
+        // about 2.53 standard deviations for 100 trials
+        assert!((emax / sample_std(&trials) - 2.5306028932016846).abs() < 1e-4);
+        let wider: Vec<f64> = trials.iter().map(|s| s * 2.0).collect();
+        assert!((expected_max_sharpe(&wider) - 2.0 * emax).abs() < 1e-12);
+    }
+
+    #[test]
+    fn deflated_sharpe_is_below_probabilistic_sharpe_after_several_trials() {
+        let r = returns();
+        let trials = [-0.05, 0.0, 0.05, 0.1];
+        assert!(deflated_sharpe(&r, &trials) < probabilistic_sharpe(&r, 0.0));
+        // a single trial is not deflated
+        assert_eq!(deflated_sharpe(&r, &[0.1]), probabilistic_sharpe(&r, 0.0));
+    }
+
+    // 10 bars per block of mean `means[p]` / 1000, alternating 0.01 either side of it
+    fn blocks(means: &[f64]) -> Vec<f64> {
+        means
+            .iter()
+            .flat_map(|m| (0..10).map(move |k| m / 1000.0 + if k % 2 == 0 { 0.01 } else { -0.01 }))
+            .collect()
+    }
+
+    #[test]
+    fn pbo_is_one_when_the_in_sample_winner_always_loses_out_of_sample() {
+        // every trial totals zero over the blocks, so the best half is followed by the worst
+        let trials = vec![
+            blocks(&[3.0, 1.0, -1.0, -3.0]),
+            blocks(&[-3.0, 3.0, 1.0, -1.0]),
+            blocks(&[-1.0, -3.0, 3.0, 1.0]),
+            blocks(&[1.0, -1.0, -3.0, 3.0]),
+        ];
+        assert_eq!(pbo(&trials, 4).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn pbo_is_zero_when_one_trial_wins_every_block() {
+        let trials = vec![
+            blocks(&[3.0, 3.0, 3.0, 3.0]),
+            blocks(&[-3.0, 2.0, 1.0, -1.0]),
+            blocks(&[1.0, -3.0, 2.0, 1.0]),
+        ];
+        assert_eq!(pbo(&trials, 4).unwrap(), 0.0);
+        assert!(pbo(&trials, 3).is_err());
+        assert!(pbo(&trials[..1], 4).is_err());
+    }
+}
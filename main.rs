@@ -2,14 +2,33 @@ mod data_reader;
 mod engiene;
 mod indicators;
 mod strategy;
+mod optimizer;
+mod random;
+mod walk_forward;
+mod cross_validation;
+mod monte_carlo;
 use std::collections::HashMap;
 use data_reader::data::data::DataFeed;
 use engiene::engiene::backtest::{PramVal, Prams, Backtest};
-use indicators::indicators::Indicators;
-use crate::strategy::strategy::Strategy;
+use indicators::indicators::{Indicators, MaType};
+use crate::strategy::strategy::{HedgeRatio, PairsStrategy, Strategy};
+use crate::data_reader::bars::bars::Bars;
+use crate::indicators::patterns::{Patterns, ALL_PATTERNS};
+use crate::indicators::stats::{adf, zscore, PairStats};
+use crate::optimizer::optimizer::{crossover_runner, export_csv, overfitting, print_table, Objective, Optimizer};
+use crate::walk_forward::walk_forward::{WalkForward, WindowMode};
+use crate::cross_validation::cross_validation::{grid_fit, CrossValidation};
+use crate::monte_carlo::monte_carlo::{MonteCarlo, Resample};
 
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::args().any(|arg| arg == "--verify-indicators") {
+        return indicators::reference::verify("indicators/fixtures");
+    }
+    if std::env::args().any(|arg| arg == "--verify-talib") {
+        return indicators::reference::verify_talib("indicators/fixtures");
+    }
+
     let mut data_feed = DataFeed::new();
     let csv_file = "BTC-USD.csv"; // Replace with actual CSV file path
     data_feed.read_csv(csv_file)?;
@@ -19,9 +38,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Err("No data available in DataFeed".into());
     }
 
-    // Create strategy with the data feed
-    let mut strategy = Strategy::new(data_feed);
-    
     // Set custom parameters
     let mut params = Prams {
         data: HashMap::new(),
@@ -31,16 +47,120 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     params.data.insert("slippage".to_string(), PramVal::Double(0.0005));
     params.data.insert("sizer".to_string(), PramVal::Double(0.02));
     
+    // Sweep the crossover periods instead of a single run
+    if std::env::args().any(|arg| arg == "--optimize") {
+        let mut optimizer = Optimizer::new(Objective::Sharpe);
+        optimizer.int_range("period1", 5, 20, 1);
+        optimizer.int_range("period2", 10, 50, 2);
+        optimizer.add_range("sizer", vec![PramVal::Double(0.02), PramVal::Double(0.05)]);
+        let results = optimizer.run(&params, crossover_runner(data_feed))?;
+        print_table(&results, 10);
+        overfitting(&results, 16)?.print();
+        export_csv(&results, "optimization.csv")?;
+        return Ok(());
+    }
+
+    if std::env::args().any(|arg| arg == "--walk-forward") {
+        let mut optimizer = Optimizer::new(Objective::Sharpe);
+        optimizer.int_range("period1", 5, 20, 1);
+        optimizer.int_range("period2", 10, 50, 2);
+        let mut walk_forward = WalkForward::new(365, 90, WindowMode::Rolling);
+        walk_forward.set_warmup(52);
+        walk_forward.set_optimised(optimizer.param_names());
+        let report = walk_forward.run(
+            &data_feed,
+            |slice| optimizer.run(&params, crossover_runner(slice)),
+            crossover_runner,
+        )?;
+        report.print();
+        return Ok(());
+    }
+
+    if std::env::args().any(|arg| arg == "--cross-validate") {
+        let mut optimizer = Optimizer::new(Objective::Sharpe);
+        optimizer.int_range("period1", 5, 20, 1);
+        optimizer.int_range("period2", 10, 50, 2);
+        let mut cv = CrossValidation::combinatorial(6, 2);
+        cv.set_purge(5);
+        cv.set_embargo(5);
+        cv.set_warmup(52);
+        let report = cv.run(&data_feed, grid_fit(&optimizer, &params, crossover_runner), crossover_runner)?;
+        report.print();
+        return Ok(());
+    }
+
+    // Trade the spread against a second feed on the same dates, e.g. --pairs ETH-USD.csv
+    if let Some(pair_file) = std::env::args().skip_while(|arg| arg != "--pairs").nth(1) {
+        let mut other = DataFeed::new();
+        other.read_csv(&pair_file)?;
+        let last = |series: Vec<f64>| series.last().copied().unwrap_or(0.0);
+        let mut pair = PairStats::new(other.get_ohlcv(), data_feed.get_ohlcv())?;
+        let coint = pair.COINTEGRATION(1)?;
+        println!("...Pair...");
+        println!("Hedge: y = {} + {} x", coint.alpha, coint.beta);
+        println!("Cointegration: {} (p {})", coint.statistic, coint.p_value);
+        println!("Spread: {} (z {})", coint.spread.last().copied().unwrap_or(0.0), last(zscore(&coint.spread, 60)?));
+        for (name, feed) in [("Y", &other), ("X", &data_feed)] {
+            let test = adf(&feed.Close, 1, true)?;
+            println!("{} ADF: {} (p {}, {} lags, {} bars)", name, test.statistic, test.p_value, test.lags, test.observations);
+        }
+        println!("Half Life: {}", pair.HALF_LIFE()?);
+        println!("Hurst: {}", pair.HURST(20)?);
+        println!("Correlation: {}", last(pair.CORRELATION(30)?));
+        println!("Rolling Beta: {}", last(pair.BETA(60)?));
+        println!("Spread Z-Score: {}", last(pair.SPREAD_ZSCORE(60)?));
+
+        let mut pairs = PairsStrategy::new(data_feed, other)?;
+        pairs.set_params(params)?;
+        // --static-hedge fits the hedge once over the first 120 bars instead
+        let hedge = if std::env::args().any(|arg| arg == "--static-hedge") {
+            HedgeRatio::Static(120)
+        } else {
+            HedgeRatio::Rolling(60)
+        };
+        pairs.next(hedge, 20, 2.0, 0.5)?;
+        pairs.run()?;
+        pairs.stats()?;
+        return Ok(());
+    }
+
+    // Create strategy with the data feed, or with Heikin-Ashi candles filling at its prices
+    let mut strategy = if std::env::args().any(|arg| arg == "--heikin-ashi") {
+        let candles = Bars { ohlcv: data_feed.get_ohlcv() }.heikin_ashi()?;
+        Strategy::from_bars(&candles, &data_feed)?
+    } else {
+        Strategy::new(data_feed)
+    };
     strategy.set_params(params)?;
     
-    // Run the strategy with SMA period of 10
-    strategy.next(9, 11)?;
+    if std::env::args().any(|arg| arg == "--patterns") {
+        // Count each pattern's bullish and bearish bars, then trade every one of at least half strength
+        let mut detector = Patterns { ohlcv: strategy.backtest.ohlcv.get_ohlcv() };
+        for pattern in ALL_PATTERNS {
+            let series = detector.signal(pattern)?;
+            let bullish = series.iter().filter(|&&s| s > 0.0).count();
+            let bearish = series.iter().filter(|&&s| s < 0.0).count();
+            println!("{:?}: {} bullish, {} bearish", pattern, bullish, bearish);
+        }
+        strategy.next_pattern(&ALL_PATTERNS, 0.5)?;
+    } else {
+        // Run the strategy with an SMA crossover of 9 and 11
+        strategy.next(9, 11, MaType::SMA)?;
+    }
     
     // Run the backtest
     strategy.run()?;
     
     // Display statistics
     strategy.stats()?;
+
+    if std::env::args().any(|arg| arg == "--monte-carlo") {
+        let monte_carlo = MonteCarlo::new(1000, 42);
+        for method in [Resample::Reshuffle, Resample::Bootstrap, Resample::SkipTrades(0.1)] {
+            monte_carlo.trades(&strategy.backtest, method)?.print();
+        }
+        monte_carlo.returns(&strategy.backtest, Resample::BlockBootstrap(20))?.print();
+    }
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,226 @@
+pub mod monte_carlo {
+    #![allow(dead_code)]
+    use crate::Backtest;
+    use crate::indicators::stats::quantile;
+    use crate::random::random::Rng;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Resample {
+        // same steps in a random order; final equity is unchanged, the path is not
+        Reshuffle,
+        // steps drawn with replacement
+        Bootstrap,
+        // runs of consecutive steps drawn with replacement, keeping short-range dependence
+        BlockBootstrap(usize),
+        // each step dropped with this probability, as if the signal had been missed
+        SkipTrades(f64),
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Distribution {
+        pub mean: f64,
+        pub median: f64,
+        // confidence interval bounds
+        pub lower: f64,
+        pub upper: f64,
+        pub values: Vec<f64>,
+    }
+
+    impl Distribution {
+        fn new(mut values: Vec<f64>, confidence: f64) -> Self {
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            let tail = (1.0 - confidence) / 2.0;
+            Distribution {
+                mean: values.iter().sum::<f64>() / values.len() as f64,
+                median: quantile(&values, 0.5),
+                lower: quantile(&values, tail),
+                upper: quantile(&values, 1.0 - tail),
+                values,
+            }
+        }
+    }
+
+    pub struct MonteCarloReport {
+        pub method: Resample,
+        pub confidence: f64,
+        pub final_equity: Distribution,
+        pub max_drawdown: Distribution,
+        // longest stretch, in steps, spent below a previous peak
+        pub time_to_recover: Distribution,
+    }
+
+    pub struct MonteCarlo {
+        pub runs: usize,
+        pub seed: u64,
+        pub confidence: f64,
+    }
+
+    impl MonteCarlo {
+        pub fn new(runs: usize, seed: u64) -> Self {
+            MonteCarlo {
+                runs,
+                seed,
+                confidence: 0.95,
+            }
+        }
+
+        pub fn set_confidence(&mut self, confidence: f64) {
+            self.confidence = confidence;
+        }
+
+        // Resamples the returns of the backtest's round trips; steps are trades
+        pub fn trades(&self, backtest: &Backtest, method: Resample) -> Result<MonteCarloReport, Box<dyn std::error::Error>> {
+            let returns: Vec<f64> = backtest.ledger.iter().map(|t| t.ret()).collect();
+            self.simulate(&returns, backtest.init_balance, method)
+        }
+
+        // Resamples the per-bar equity returns; steps are bars
+        pub fn returns(&self, backtest: &Backtest, method: Resample) -> Result<MonteCarloReport, Box<dyn std::error::Error>> {
+            self.simulate(&backtest.returns(), backtest.init_balance, method)
+        }
+
+        pub fn simulate(&self, returns: &[f64], start: f64, method: Resample) -> Result<MonteCarloReport, Box<dyn std::error::Error>> {
+            if returns.is_empty() {
+                return Err("No returns to resample".into());
+            }
+            if self.runs == 0 {
+                return Err("Need at least one run".into());
+            }
+            match method {
+                Resample::BlockBootstrap(0) => return Err("Block length must be positive".into()),
+                Resample::SkipTrades(p) if !(0.0..1.0).contains(&p) => return Err("Skip probability must be in [0, 1)".into()),
+                _ => {}
+            }
+
+            let mut rng = Rng::new(self.seed);
+            let mut finals = Vec::with_capacity(self.runs);
+            let mut drawdowns = Vec::with_capacity(self.runs);
+            let mut recoveries = Vec::with_capacity(self.runs);
+            for _ in 0..self.runs {
+                let path = resample(returns, method, &mut rng);
+                let (final_equity, max_dd, recover) = path_stats(&path, start);
+                finals.push(final_equity);
+                drawdowns.push(max_dd);
+                recoveries.push(recover as f64);
+            }
+            Ok(MonteCarloReport {
+                method,
+                confidence: self.confidence,
+                final_equity: Distribution::new(finals, self.confidence),
+                max_drawdown: Distribution::new(drawdowns, self.confidence),
+                time_to_recover: Distribution::new(recoveries, self.confidence),
+            })
+        }
+    }
+
+    fn resample(returns: &[f64], method: Resample, rng: &mut Rng) -> Vec<f64> {
+        let n = returns.len();
+        match method {
+            Resample::Reshuffle => {
+                let mut path = returns.to_vec();
+                rng.shuffle(&mut path);
+                path
+            }
+            Resample::Bootstrap => (0..n).map(|_| returns[rng.below(n)]).collect(),
+            Resample::BlockBootstrap(block) => {
+                // blocks wrap around the end so every step is equally likely to be drawn
+                let mut path = Vec::with_capacity(n);
+                while path.len() < n {
+                    let start = rng.below(n);
+                    for k in 0..block.min(n - path.len()) {
+                        path.push(returns[(start + k) % n]);
+                    }
+                }
+                path
+            }
+            Resample::SkipTrades(p) => returns.iter().cloned().filter(|_| !rng.chance(p)).collect(),
+        }
+    }
+
+    // (final equity, max drawdown, longest time under water) of `returns` compounded from `start`
+    fn path_stats(returns: &[f64], start: f64) -> (f64, f64, usize) {
+        let mut equity = start;
+        let mut peak = start;
+        let mut max_dd: f64 = 0.0;
+        let mut under = 0;
+        let mut longest = 0;
+        for r in returns {
+            equity *= 1.0 + r;
+            if equity >= peak {
+                peak = equity;
+                under = 0;
+            } else {
+                under += 1;
+                longest = longest.max(under);
+                if peak > 0.0 {
+                    max_dd = max_dd.max((peak - equity) / peak);
+                }
+            }
+        }
+        (equity, max_dd, longest)
+    }
+
+    impl MonteCarloReport {
+        pub fn print(&self) {
+            println!("...Monte Carlo: {:?}...", self.method);
+            for (label, d) in [
+                ("Final Equity", &self.final_equity),
+                ("Max Drawdown", &self.max_drawdown),
+                ("Time To Recover", &self.time_to_recover),
+            ] {
+                println!(
+                    "{}: mean {:.4} median {:.4} {:.0}% CI [{:.4}, {:.4}]",
+                    label, d.mean, d.median, self.confidence * 100.0, d.lower, d.upper
+                );
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const RETURNS: [f64; 6] = [0.05, -0.1, 0.02, 0.08, -0.03, -0.06];
+
+        fn all_equal(values: &[f64], expected: f64) -> bool {
+            values.iter().all(|v| (v - expected).abs() < 1e-9 * expected.abs().max(1.0))
+        }
+
+        #[test]
+        fn reshuffle_keeps_final_equity() {
+            let (final_equity, _, _) = path_stats(&RETURNS, 1000.0);
+            let report = MonteCarlo::new(200, 7).simulate(&RETURNS, 1000.0, Resample::Reshuffle).unwrap();
+            assert!(all_equal(&report.final_equity.values, final_equity));
+            // the order, and with it the drawdown, does change
+            assert!(report.max_drawdown.lower < report.max_drawdown.upper);
+        }
+
+        #[test]
+        fn a_fixed_seed_reproduces_the_runs() {
+            let run = |seed| MonteCarlo::new(100, seed).simulate(&RETURNS, 1000.0, Resample::Bootstrap).unwrap();
+            let (first, again, other) = (run(42), run(42), run(43));
+            assert_eq!(first.final_equity.values, again.final_equity.values);
+            assert_eq!(first.max_drawdown.values, again.max_drawdown.values);
+            assert_ne!(first.final_equity.values, other.final_equity.values);
+        }
+
+        #[test]
+        fn block_longer_than_the_trades_wraps_into_one_rotation() {
+            let (final_equity, _, _) = path_stats(&RETURNS, 1000.0);
+            let report = MonteCarlo::new(50, 3).simulate(&RETURNS, 1000.0, Resample::BlockBootstrap(20)).unwrap();
+            // every path is the trades started at a random one, so it compounds to the same end
+            assert!(all_equal(&report.final_equity.values, final_equity));
+            assert!(MonteCarlo::new(50, 3).simulate(&RETURNS, 1000.0, Resample::BlockBootstrap(0)).is_err());
+        }
+
+        #[test]
+        fn skipping_no_trades_matches_the_original() {
+            let (final_equity, max_dd, recover) = path_stats(&RETURNS, 1000.0);
+            let report = MonteCarlo::new(50, 5).simulate(&RETURNS, 1000.0, Resample::SkipTrades(0.0)).unwrap();
+            assert!(all_equal(&report.final_equity.values, final_equity));
+            assert!(all_equal(&report.max_drawdown.values, max_dd));
+            assert!(all_equal(&report.time_to_recover.values, recover as f64));
+            assert!(MonteCarlo::new(50, 5).simulate(&RETURNS, 1000.0, Resample::SkipTrades(1.0)).is_err());
+        }
+    }
+}
@@ -0,0 +1,776 @@
+pub mod optimizer {
+    #![allow(dead_code)]
+    use crate::{Backtest, DataFeed, MaType, PramVal, Prams};
+    use crate::strategy::strategy::Strategy;
+    use crate::random::random::Rng;
+    use crate::indicators::stats::{deflated_sharpe, expected_max_sharpe, pbo, probabilistic_sharpe, sharpe_ratio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Objective {
+        Sharpe,
+        Return,
+        // total return divided by max drawdown
+        ReturnOverDrawdown,
+    }
+
+    pub struct ParamRange {
+        pub name: String,
+        pub values: Vec<PramVal>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum ParamKind {
+        Int(i32, i32),
+        Float(f64, f64),
+        Categorical(Vec<PramVal>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ParamSpec {
+        pub name: String,
+        pub kind: ParamKind,
+        // only sampled when the named parameter holds this value
+        pub condition: Option<(String, PramVal)>,
+    }
+
+    // Typed search space for the random, genetic and Bayesian searches. A conditional
+    // parameter must be added after the parameter it depends on.
+    #[derive(Debug, Clone)]
+    pub struct ParamSpace {
+        pub specs: Vec<ParamSpec>,
+    }
+
+    impl ParamSpace {
+        pub fn new() -> Self {
+            ParamSpace { specs: Vec::new() }
+        }
+
+        pub fn add_int(&mut self, name: &str, low: i32, high: i32) {
+            self.push(name, ParamKind::Int(low.min(high), low.max(high)));
+        }
+
+        pub fn add_float(&mut self, name: &str, low: f64, high: f64) {
+            self.push(name, ParamKind::Float(low.min(high), low.max(high)));
+        }
+
+        pub fn add_categorical(&mut self, name: &str, choices: Vec<PramVal>) {
+            self.push(name, ParamKind::Categorical(choices));
+        }
+
+        pub fn add_condition(&mut self, name: &str, parent: &str, value: PramVal) -> Result<(), Box<dyn std::error::Error>> {
+            let parent_idx = self.specs.iter().position(|s| s.name == parent).ok_or("Unknown parent parameter")?;
+            let idx = self.specs.iter().position(|s| s.name == name).ok_or("Unknown parameter")?;
+            if parent_idx >= idx {
+                return Err("A conditional parameter must come after its parent".into());
+            }
+            self.specs[idx].condition = Some((parent.to_string(), value));
+            Ok(())
+        }
+
+        fn push(&mut self, name: &str, kind: ParamKind) {
+            self.specs.push(ParamSpec {
+                name: name.to_string(),
+                kind,
+                condition: None,
+            });
+        }
+
+        fn is_active(spec: &ParamSpec, prams: &Prams) -> bool {
+            match &spec.condition {
+                Some((parent, value)) => prams.data.get(parent) == Some(value),
+                None => true,
+            }
+        }
+
+        fn sample_value(kind: &ParamKind, rng: &mut Rng) -> PramVal {
+            match kind {
+                ParamKind::Int(low, high) => PramVal::Int(rng.int_range(*low as i64, *high as i64) as i32),
+                ParamKind::Float(low, high) => PramVal::Double(rng.uniform(*low, *high)),
+                ParamKind::Categorical(choices) => choices[rng.below(choices.len())].clone(),
+            }
+        }
+
+        pub fn sample(&self, base: &Prams, rng: &mut Rng) -> Prams {
+            let mut prams = base.clone();
+            for spec in &self.specs {
+                if Self::is_active(spec, &prams) {
+                    prams.data.insert(spec.name.clone(), Self::sample_value(&spec.kind, rng));
+                }
+            }
+            prams
+        }
+
+        // Samples parameters that became active and drops ones that became inactive
+        fn repair(&self, prams: &mut Prams, rng: &mut Rng) {
+            for spec in &self.specs {
+                let active = Self::is_active(spec, prams);
+                if active && !prams.data.contains_key(&spec.name) {
+                    prams.data.insert(spec.name.clone(), Self::sample_value(&spec.kind, rng));
+                } else if !active {
+                    prams.data.remove(&spec.name);
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct GeneticConfig {
+        pub population: usize,
+        pub elite: usize,
+        pub tournament: usize,
+        pub crossover_rate: f64,
+        pub mutation_rate: f64,
+    }
+
+    impl GeneticConfig {
+        pub fn new() -> Self {
+            GeneticConfig {
+                population: 20,
+                elite: 2,
+                tournament: 3,
+                crossover_rate: 0.9,
+                mutation_rate: 0.2,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct OptResult {
+        pub params: Prams,
+        pub total_return: f64,
+        pub sharpe: f64,
+        pub max_drawdown: f64,
+        pub trades: usize,
+        pub objective: f64,
+        pub equity: Vec<f64>,
+    }
+
+    pub struct Optimizer {
+        pub ranges: Vec<ParamRange>,
+        pub objective: Objective,
+        pub threads: usize,
+        pub periods_per_year: f64,
+        // proposals per Bayesian round; fixed so a seed gives the same search on any machine
+        pub batch: usize,
+    }
+
+    impl Optimizer {
+        pub fn new(objective: Objective) -> Self {
+            Optimizer {
+                ranges: Vec::new(),
+                objective,
+                threads: std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+                periods_per_year: 365.0,
+                batch: 4,
+            }
+        }
+
+        pub fn set_batch(&mut self, batch: usize) {
+            self.batch = batch.max(1);
+        }
+
+        pub fn add_range(&mut self, name: &str, values: Vec<PramVal>) {
+            self.ranges.push(ParamRange {
+                name: name.to_string(),
+                values,
+            });
+        }
+
+        // Names of the parameters the grid varies
+        pub fn param_names(&self) -> Vec<String> {
+            self.ranges.iter().map(|r| r.name.clone()).collect()
+        }
+
+        pub fn int_range(&mut self, name: &str, start: i32, end: i32, step: i32) {
+            let values = (start..=end).step_by(step.max(1) as usize).map(PramVal::Int).collect();
+            self.add_range(name, values);
+        }
+
+        pub fn double_range(&mut self, name: &str, start: f64, end: f64, step: f64) {
+            let mut values = Vec::new();
+            let mut v = start;
+            while v <= end + step * 1e-9 {
+                values.push(PramVal::Double(v));
+                v += step;
+            }
+            self.add_range(name, values);
+        }
+
+        // Every combination of the ranges, on top of `base` (e.g. balance and commission)
+        pub fn grid(&self, base: &Prams) -> Vec<Prams> {
+            let mut combos = vec![base.clone()];
+            for range in &self.ranges {
+                let mut next = Vec::with_capacity(combos.len() * range.values.len());
+                for combo in &combos {
+                    for value in &range.values {
+                        let mut prams = combo.clone();
+                        prams.data.insert(range.name.clone(), value.clone());
+                        next.push(prams);
+                    }
+                }
+                combos = next;
+            }
+            combos
+        }
+
+        pub fn run<F>(&self, base: &Prams, runner: F) -> Result<Vec<OptResult>, Box<dyn std::error::Error>>
+        where
+            F: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync,
+        {
+            let results = self.evaluate(self.grid(base), &runner)?;
+            Ok(self.rank(results))
+        }
+
+        pub fn rank(&self, mut results: Vec<OptResult>) -> Vec<OptResult> {
+            results.sort_by(|a, b| b.objective.partial_cmp(&a.objective).unwrap_or(std::cmp::Ordering::Equal));
+            results
+        }
+
+        // Samples `budget` parameter sets uniformly from the space
+        pub fn random_search<F>(&self, space: &ParamSpace, budget: usize, seed: u64, base: &Prams, runner: F) -> Result<Vec<OptResult>, Box<dyn std::error::Error>>
+        where
+            F: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync,
+        {
+            let mut rng = Rng::new(seed);
+            let combos = (0..budget).map(|_| space.sample(base, &mut rng)).collect();
+            let results = self.evaluate(combos, &runner)?;
+            Ok(self.rank(results))
+        }
+
+        // Generational GA with elitism, tournament selection, uniform crossover and
+        // per-gene mutation; stops once `budget` runs have been spent
+        pub fn genetic<F>(&self, space: &ParamSpace, config: &GeneticConfig, budget: usize, seed: u64, base: &Prams, runner: F) -> Result<Vec<OptResult>, Box<dyn std::error::Error>>
+        where
+            F: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync,
+        {
+            if config.population < 2 {
+                return Err("Population must hold at least two members".into());
+            }
+            let mut rng = Rng::new(seed);
+            let first = (0..config.population.min(budget)).map(|_| space.sample(base, &mut rng)).collect();
+            let mut spent = config.population.min(budget);
+            let mut population = self.rank(self.evaluate(first, &runner)?);
+            let mut history = population.clone();
+
+            while spent < budget {
+                let children_count = (config.population - config.elite.min(config.population)).min(budget - spent);
+                let mut children = Vec::with_capacity(children_count);
+                for _ in 0..children_count {
+                    let a = Self::tournament(&population, config.tournament, &mut rng);
+                    let b = Self::tournament(&population, config.tournament, &mut rng);
+                    let mut child = a.params.clone();
+                    if rng.chance(config.crossover_rate) {
+                        for spec in &space.specs {
+                            if rng.chance(0.5) {
+                                match b.params.data.get(&spec.name) {
+                                    Some(v) => child.data.insert(spec.name.clone(), v.clone()),
+                                    None => child.data.remove(&spec.name),
+                                };
+                            }
+                        }
+                    }
+                    for spec in &space.specs {
+                        if rng.chance(config.mutation_rate) {
+                            if let Some(v) = child.data.get(&spec.name).cloned() {
+                                child.data.insert(spec.name.clone(), Self::mutate(&spec.kind, &v, &mut rng));
+                            }
+                        }
+                    }
+                    space.repair(&mut child, &mut rng);
+                    children.push(child);
+                }
+                spent += children.len();
+                let evaluated = self.evaluate(children, &runner)?;
+                history.extend(evaluated.iter().cloned());
+
+                let mut next: Vec<OptResult> = population.iter().take(config.elite).cloned().collect();
+                next.extend(evaluated);
+                population = self.rank(next);
+            }
+            Ok(self.rank(history))
+        }
+
+        fn tournament<'a>(population: &'a [OptResult], size: usize, rng: &mut Rng) -> &'a OptResult {
+            let mut best = &population[rng.below(population.len())];
+            for _ in 1..size.max(1) {
+                let other = &population[rng.below(population.len())];
+                if other.objective > best.objective {
+                    best = other;
+                }
+            }
+            best
+        }
+
+        fn mutate(kind: &ParamKind, value: &PramVal, rng: &mut Rng) -> PramVal {
+            match (kind, value) {
+                (ParamKind::Int(low, high), PramVal::Int(v)) => {
+                    let step = (((high - low) as f64 * 0.1).max(1.0) * rng.normal()).round() as i32;
+                    PramVal::Int((v + step).clamp(*low, *high))
+                }
+                (ParamKind::Float(low, high), PramVal::Double(v)) => {
+                    PramVal::Double((v + (high - low) * 0.1 * rng.normal()).clamp(*low, *high))
+                }
+                _ => ParamSpace::sample_value(kind, rng),
+            }
+        }
+
+        // Tree-structured Parzen estimator: after `startup` random runs, observations are split
+        // into the best quarter and the rest, and each batch of `batch` candidates is drawn from
+        // the density of the good runs, keeping those with the highest good/bad density ratio
+        pub fn bayesian<F>(&self, space: &ParamSpace, budget: usize, startup: usize, seed: u64, base: &Prams, runner: F) -> Result<Vec<OptResult>, Box<dyn std::error::Error>>
+        where
+            F: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync,
+        {
+            const GAMMA: f64 = 0.25;
+            const CANDIDATES: usize = 24;
+            let mut rng = Rng::new(seed);
+            let first = startup.max(2).min(budget);
+            let initial = (0..first).map(|_| space.sample(base, &mut rng)).collect();
+            let mut history = self.evaluate(initial, &runner)?;
+            let mut spent = first;
+            let batch = self.batch.max(1);
+
+            while spent < budget {
+                let ranked = self.rank(history.clone());
+                let n_good = ((ranked.len() as f64 * GAMMA).ceil() as usize).clamp(1, ranked.len());
+                let (good, bad) = ranked.split_at(n_good);
+
+                let size = batch.min(budget - spent);
+                let mut proposals = Vec::with_capacity(size);
+                for _ in 0..size {
+                    let mut best: Option<(f64, Prams)> = None;
+                    for _ in 0..CANDIDATES {
+                        let mut candidate = base.clone();
+                        let mut score = 0.0;
+                        for spec in &space.specs {
+                            if !ParamSpace::is_active(spec, &candidate) {
+                                continue;
+                            }
+                            let good_values: Vec<&PramVal> = good.iter().filter_map(|r| r.params.data.get(&spec.name)).collect();
+                            let bad_values: Vec<&PramVal> = bad.iter().filter_map(|r| r.params.data.get(&spec.name)).collect();
+                            let value = Self::parzen_sample(&spec.kind, &good_values, &mut rng);
+                            score += Self::parzen_density(&spec.kind, &good_values, &value).ln()
+                                - Self::parzen_density(&spec.kind, &bad_values, &value).ln();
+                            candidate.data.insert(spec.name.clone(), value);
+                        }
+                        if best.as_ref().is_none_or(|(s, _)| score > *s) {
+                            best = Some((score, candidate));
+                        }
+                    }
+                    if let Some((_, candidate)) = best {
+                        proposals.push(candidate);
+                    }
+                }
+                spent += proposals.len();
+                history.extend(self.evaluate(proposals, &runner)?);
+            }
+            Ok(self.rank(history))
+        }
+
+        fn numeric(value: &PramVal) -> Option<f64> {
+            match value {
+                PramVal::Int(v) => Some(*v as f64),
+                PramVal::Double(v) => Some(*v),
+                _ => None,
+            }
+        }
+
+        // Kernel bandwidth for numeric parameters; never narrower than 5% of the range
+        fn bandwidth(low: f64, high: f64, n: usize) -> f64 {
+            ((high - low) / (n as f64 + 1.0).powf(0.2) * 0.5).max((high - low) * 0.05).max(1e-12)
+        }
+
+        // One draw from the mixture of a uniform prior and a Gaussian kernel per observation
+        fn parzen_sample(kind: &ParamKind, observed: &[&PramVal], rng: &mut Rng) -> PramVal {
+            let pick = rng.below(observed.len() + 1);
+            match kind {
+                ParamKind::Int(low, high) => {
+                    if pick == observed.len() {
+                        return ParamSpace::sample_value(kind, rng);
+                    }
+                    let centre = Self::numeric(observed[pick]).unwrap_or(*low as f64);
+                    let h = Self::bandwidth(*low as f64, *high as f64, observed.len());
+                    PramVal::Int(((centre + h * rng.normal()).round() as i32).clamp(*low, *high))
+                }
+                ParamKind::Float(low, high) => {
+                    if pick == observed.len() {
+                        return ParamSpace::sample_value(kind, rng);
+                    }
+                    let centre = Self::numeric(observed[pick]).unwrap_or(*low);
+                    let h = Self::bandwidth(*low, *high, observed.len());
+                    PramVal::Double((centre + h * rng.normal()).clamp(*low, *high))
+                }
+                ParamKind::Categorical(_) => {
+                    if pick == observed.len() {
+                        ParamSpace::sample_value(kind, rng)
+                    } else {
+                        observed[pick].clone()
+                    }
+                }
+            }
+        }
+
+        fn parzen_density(kind: &ParamKind, observed: &[&PramVal], value: &PramVal) -> f64 {
+            let n = observed.len() as f64;
+            let density = match kind {
+                ParamKind::Int(low, high) => Self::kernel_density(*low as f64, *high as f64, observed, value),
+                ParamKind::Float(low, high) => Self::kernel_density(*low, *high, observed, value),
+                ParamKind::Categorical(choices) => {
+                    let count = observed.iter().filter(|v| **v == value).count() as f64;
+                    (count + 1.0) / (n + choices.len() as f64)
+                }
+            };
+            density.max(1e-300)
+        }
+
+        fn kernel_density(low: f64, high: f64, observed: &[&PramVal], value: &PramVal) -> f64 {
+            let x = Self::numeric(value).unwrap_or(low);
+            let h = Self::bandwidth(low, high, observed.len());
+            let prior = 1.0 / (high - low).max(1.0);
+            let kernels: f64 = observed
+                .iter()
+                .filter_map(|v| Self::numeric(v))
+                .map(|c| (-0.5 * ((x - c) / h).powi(2)).exp() / (h * (2.0 * std::f64::consts::PI).sqrt()))
+                .sum();
+            (prior + kernels) / (observed.len() as f64 + 1.0)
+        }
+
+        // Runs each parameter set on its own freshly built Backtest across `threads` workers.
+        // Combinations whose run fails (e.g. period1 >= period2) are dropped.
+        pub fn evaluate<F>(&self, combos: Vec<Prams>, runner: &F) -> Result<Vec<OptResult>, Box<dyn std::error::Error>>
+        where
+            F: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync,
+        {
+            if combos.is_empty() {
+                return Err("No parameter combinations to run".into());
+            }
+            let next = AtomicUsize::new(0);
+            let results: Mutex<Vec<(usize, OptResult)>> = Mutex::new(Vec::with_capacity(combos.len()));
+            let failures: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+            std::thread::scope(|scope| {
+                for _ in 0..self.threads.max(1).min(combos.len()) {
+                    scope.spawn(|| loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        if i >= combos.len() {
+                            break;
+                        }
+                        let mut prams = combos[i].clone();
+                        prams.data.insert("verbose".to_string(), PramVal::Bool(false));
+                        match runner(&prams) {
+                            Ok(backtest) => {
+                                prams.data.remove("verbose");
+                                let result = self.score(prams, &backtest);
+                                results.lock().unwrap().push((i, result));
+                            }
+                            Err(e) => failures.lock().unwrap().push(e.to_string()),
+                        }
+                    });
+                }
+            });
+
+            let mut results = results.into_inner().map_err(|_| "Optimizer worker panicked")?;
+            if results.is_empty() {
+                let failures = failures.into_inner().map_err(|_| "Optimizer worker panicked")?;
+                return Err(format!("Every run failed: {}", failures.first().cloned().unwrap_or_default()).into());
+            }
+            results.sort_by_key(|(i, _)| *i);
+            Ok(results.into_iter().map(|(_, r)| r).collect())
+        }
+
+        fn score(&self, params: Prams, backtest: &Backtest) -> OptResult {
+            let total_return = backtest.total_return();
+            let sharpe = backtest.sharpe(self.periods_per_year);
+            let max_drawdown = backtest.max_drawdown();
+            let objective = match self.objective {
+                Objective::Sharpe => sharpe,
+                Objective::Return => total_return,
+                Objective::ReturnOverDrawdown => {
+                    if max_drawdown > 0.0 {
+                        total_return / max_drawdown
+                    } else {
+                        total_return
+                    }
+                }
+            };
+            OptResult {
+                params,
+                total_return,
+                sharpe,
+                max_drawdown,
+                trades: backtest.trades,
+                objective,
+                equity: backtest.equity.clone(),
+            }
+        }
+    }
+
+    fn param_names(results: &[OptResult]) -> Vec<String> {
+        let mut names: Vec<String> = results
+            .iter()
+            .flat_map(|r| r.params.data.keys().cloned())
+            .collect::<std::collections::HashSet<_>>()
+            .into_iter()
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn print_table(results: &[OptResult], top: usize) {
+        let names = param_names(results);
+        let mut header: Vec<String> = names.clone();
+        header.extend(["objective", "return", "sharpe", "max_dd", "trades"].iter().map(|s| s.to_string()));
+        println!("{}", header.iter().map(|h| format!("{:<12}", h)).collect::<Vec<_>>().join(" | "));
+        for r in results.iter().take(top) {
+            let mut row: Vec<String> = names
+                .iter()
+                .map(|n| r.params.data.get(n).map(|v| v.to_string()).unwrap_or_default())
+                .collect();
+            row.push(format!("{:.4}", r.objective));
+            row.push(format!("{:.4}", r.total_return));
+            row.push(format!("{:.4}", r.sharpe));
+            row.push(format!("{:.4}", r.max_drawdown));
+            row.push(r.trades.to_string());
+            println!("{}", row.iter().map(|c| format!("{:<12}", c)).collect::<Vec<_>>().join(" | "));
+        }
+    }
+
+    pub fn export_csv(results: &[OptResult], filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let names = param_names(results);
+        let mut writer = csv::Writer::from_path(filename)?;
+        let mut header = names.clone();
+        header.extend(["objective", "return", "sharpe", "max_drawdown", "trades"].iter().map(|s| s.to_string()));
+        writer.write_record(&header)?;
+        for r in results {
+            let mut row: Vec<String> = names
+                .iter()
+                .map(|n| r.params.data.get(n).map(|v| v.to_string()).unwrap_or_default())
+                .collect();
+            row.push(r.objective.to_string());
+            row.push(r.total_return.to_string());
+            row.push(r.sharpe.to_string());
+            row.push(r.max_drawdown.to_string());
+            row.push(r.trades.to_string());
+            writer.write_record(&row)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Luck-versus-skill diagnostics for the top result of a ranked sweep
+    #[derive(Debug, Clone)]
+    pub struct Overfitting {
+        pub trials: usize,
+        // probability the top result's true Sharpe ratio is above zero
+        pub psr: f64,
+        // the same, against the best Sharpe ratio expected from `trials` unskilled runs
+        pub dsr: f64,
+        // per bar, as used by the deflated Sharpe ratio
+        pub expected_max_sharpe: f64,
+        // None when the runs are too short for the number of partitions
+        pub pbo: Option<f64>,
+    }
+
+    impl Overfitting {
+        pub fn print(&self) {
+            println!("...Overfitting...");
+            println!("Trials: {}", self.trials);
+            println!("Probabilistic Sharpe: {:.4}", self.psr);
+            println!("Deflated Sharpe: {:.4}", self.dsr);
+            println!("Expected Max Sharpe (per bar): {:.4}", self.expected_max_sharpe);
+            match self.pbo {
+                Some(pbo) => println!("PBO: {:.4}", pbo),
+                None => println!("PBO: n/a"),
+            }
+        }
+    }
+
+    fn equity_returns(equity: &[f64]) -> Vec<f64> {
+        equity
+            .windows(2)
+            .map(|w| if w[0] != 0.0 { w[1] / w[0] - 1.0 } else { 0.0 })
+            .collect()
+    }
+
+    // `results` as ranked by the optimizer, best first. Runs warm up for different numbers
+    // of bars, so PBO compares them over the bars every equity curve covers.
+    pub fn overfitting(results: &[OptResult], partitions: usize) -> Result<Overfitting, Box<dyn std::error::Error>> {
+        if results.is_empty() {
+            return Err("No optimization results".into());
+        }
+        let returns: Vec<Vec<f64>> = results.iter().map(|r| equity_returns(&r.equity)).collect();
+        let trial_sharpes: Vec<f64> = returns.iter().map(|r| sharpe_ratio(r, 1.0)).collect();
+
+        let common = returns.iter().map(|r| r.len()).min().unwrap_or(0);
+        let aligned: Vec<Vec<f64>> = returns.iter().map(|r| r[r.len() - common..].to_vec()).collect();
+
+        Ok(Overfitting {
+            trials: results.len(),
+            psr: probabilistic_sharpe(&returns[0], 0.0),
+            dsr: deflated_sharpe(&returns[0], &trial_sharpes),
+            expected_max_sharpe: expected_max_sharpe(&trial_sharpes),
+            pbo: pbo(&aligned, partitions).ok(),
+        })
+    }
+
+    fn get_int(prams: &Prams, key: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        match prams.data.get(key) {
+            Some(PramVal::Int(v)) if *v > 0 => Ok(*v as usize),
+            _ => Err(format!("Missing or invalid integer parameter {}", key).into()),
+        }
+    }
+
+    // Runner for the moving-average crossover: reads "period1", "period2" and optional
+    // "ma_type", passes the rest to Backtest::set_params
+    pub fn crossover_runner(data: DataFeed) -> impl Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync {
+        move |prams: &Prams| {
+            let period1 = get_int(prams, "period1")?;
+            let period2 = get_int(prams, "period2")?;
+            if period1 >= period2 {
+                return Err("period1 must be shorter than period2".into());
+            }
+            let ma_type = match prams.data.get("ma_type") {
+                Some(PramVal::Str(name)) => MaType::from_name(name).ok_or("Unknown ma_type")?,
+                _ => MaType::SMA,
+            };
+            let mut strategy = Strategy::new(data.get_ohlcv());
+            strategy.set_params(prams.clone())?;
+            strategy.next(period1, period2, ma_type)?;
+            strategy.run()?;
+            Ok(strategy.backtest)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::data_reader::synthetic::synthetic::{Generator, Model};
+        use std::collections::HashMap;
+
+        // Backtest whose return peaks at x = 3; fails once `limit` runs have been made
+        fn runner(calls: &AtomicUsize, limit: usize) -> impl Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> + Sync + '_ {
+            move |prams: &Prams| {
+                if calls.fetch_add(1, Ordering::SeqCst) >= limit {
+                    return Err("runner broke".into());
+                }
+                let x = match prams.data.get("x") {
+                    Some(PramVal::Double(x)) => *x,
+                    _ => return Err("missing x".into()),
+                };
+                let mut backtest = Backtest::new(DataFeed::new());
+                backtest.init_balance = 100.0;
+                backtest.final_balance = 100.0 - (x - 3.0).powi(2);
+                backtest.equity = vec![100.0, backtest.final_balance];
+                Ok(backtest)
+            }
+        }
+
+        fn space() -> ParamSpace {
+            let mut space = ParamSpace::new();
+            space.add_float("x", 0.0, 10.0);
+            space
+        }
+
+        fn base() -> Prams {
+            Prams { data: HashMap::new() }
+        }
+
+        #[test]
+        fn bayesian_search_does_not_depend_on_threads() {
+            let searches: Vec<Vec<f64>> = [1, 3, 8]
+                .iter()
+                .map(|&threads| {
+                    let mut optimizer = Optimizer::new(Objective::Return);
+                    optimizer.threads = threads;
+                    let calls = AtomicUsize::new(0);
+                    let results = optimizer.bayesian(&space(), 30, 8, 42, &base(), runner(&calls, usize::MAX)).unwrap();
+                    results.iter().map(|r| r.objective).collect()
+                })
+                .collect();
+            assert_eq!(searches[0].len(), 30);
+            assert_eq!(searches[0], searches[1]);
+            assert_eq!(searches[0], searches[2]);
+        }
+
+        #[test]
+        fn searches_fail_when_the_runner_breaks() {
+            let optimizer = Optimizer::new(Objective::Return);
+            let calls = AtomicUsize::new(0);
+            let config = GeneticConfig::new();
+            assert!(optimizer.genetic(&space(), &config, 60, 1, &base(), runner(&calls, config.population)).is_err());
+
+            let calls = AtomicUsize::new(0);
+            assert!(optimizer.bayesian(&space(), 30, 8, 1, &base(), runner(&calls, 8)).is_err());
+        }
+
+        #[test]
+        fn a_combination_scores_the_same_first_or_last_in_the_grid() {
+            let data = Generator::new(5, 300).generate(&Model::Gbm { mu: 0.0005, sigma: 0.02 }).unwrap();
+            let mut optimizer = Optimizer::new(Objective::Sharpe);
+            optimizer.threads = 1;
+            optimizer.int_range("period1", 3, 9, 3);
+            optimizer.int_range("period2", 20, 40, 10);
+            let mut base = base();
+            base.data.insert("balance".to_string(), PramVal::Double(100000.0));
+            base.data.insert("commission".to_string(), PramVal::Double(0.001));
+            base.data.insert("sizer".to_string(), PramVal::Double(0.5));
+            let runner = crossover_runner(data);
+
+            let grid = optimizer.grid(&base);
+            let forward = optimizer.evaluate(grid.clone(), &runner).unwrap();
+            let backward = optimizer.evaluate(grid.iter().rev().cloned().collect(), &runner).unwrap();
+            let alone = optimizer.evaluate(vec![grid[grid.len() - 1].clone()], &runner).unwrap();
+            assert_eq!(forward.len(), grid.len());
+            for (a, b) in forward.iter().zip(backward.iter().rev()) {
+                assert_eq!(a.objective, b.objective);
+                assert_eq!(a.equity, b.equity);
+                assert_eq!(a.trades, b.trades);
+            }
+            assert_eq!(forward[forward.len() - 1].equity, alone[0].equity);
+            assert!(forward.iter().all(|r| r.trades > 0));
+            // the combinations really differ, so a leak would show
+            assert!(forward.iter().any(|r| r.objective != forward[0].objective));
+        }
+
+        // Backtest following one of three equity curves, picked by "x"
+        fn curve_runner(prams: &Prams) -> Result<Backtest, Box<dyn std::error::Error>> {
+            let equity = match prams.data.get("x") {
+                // biggest return, deepest drawdown
+                Some(PramVal::Int(0)) => vec![100.0, 150.0, 120.0],
+                // smaller, steadier return
+                Some(PramVal::Int(1)) => vec![100.0, 105.0, 104.0, 112.0],
+                // a loss
+                Some(PramVal::Int(2)) => vec![100.0, 90.0, 95.0],
+                _ => return Err("missing x".into()),
+            };
+            let mut backtest = Backtest::new(DataFeed::new());
+            backtest.init_balance = 100.0;
+            backtest.final_balance = equity[equity.len() - 1];
+            backtest.equity = equity;
+            Ok(backtest)
+        }
+
+        #[test]
+        fn results_are_ranked_by_the_objective_best_first() {
+            let order = |objective| -> Vec<i32> {
+                let mut optimizer = Optimizer::new(objective);
+                optimizer.int_range("x", 0, 2, 1);
+                optimizer
+                    .run(&base(), curve_runner)
+                    .unwrap()
+                    .iter()
+                    .map(|r| match r.params.data.get("x") {
+                        Some(PramVal::Int(x)) => *x,
+                        _ => -1,
+                    })
+                    .collect()
+            };
+            assert_eq!(order(Objective::Return), vec![0, 1, 2]);
+            // 0.2 / 0.2 against 0.12 / (1 / 105)
+            assert_eq!(order(Objective::ReturnOverDrawdown), vec![1, 0, 2]);
+            assert_eq!(order(Objective::Sharpe), vec![1, 0, 2]);
+        }
+    }
+}
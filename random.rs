@@ -0,0 +1,71 @@
+pub mod random {
+    #![allow(dead_code)]
+
+    // SplitMix64: small, fast and fully reproducible from its seed
+    #[derive(Debug, Clone)]
+    pub struct Rng {
+        state: u64,
+    }
+
+    impl Rng {
+        pub fn new(seed: u64) -> Self {
+            Rng { state: seed }
+        }
+
+        pub fn next_u64(&mut self) -> u64 {
+            self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = self.state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        }
+
+        // Uniform in [0, 1)
+        pub fn next_f64(&mut self) -> f64 {
+            (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+        }
+
+        pub fn uniform(&mut self, low: f64, high: f64) -> f64 {
+            low + (high - low) * self.next_f64()
+        }
+
+        // Uniform in [0, n)
+        pub fn below(&mut self, n: usize) -> usize {
+            if n == 0 {
+                return 0;
+            }
+            (self.next_f64() * n as f64) as usize % n
+        }
+
+        // Uniform integer in [low, high]
+        pub fn int_range(&mut self, low: i64, high: i64) -> i64 {
+            if high <= low {
+                return low;
+            }
+            low + self.below((high - low + 1) as usize) as i64
+        }
+
+        pub fn chance(&mut self, p: f64) -> bool {
+            self.next_f64() < p
+        }
+
+        // Standard normal via Box-Muller
+        pub fn normal(&mut self) -> f64 {
+            let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+            let u2 = self.next_f64();
+            (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+        }
+
+        pub fn shuffle<T>(&mut self, items: &mut [T]) {
+            for i in (1..items.len()).rev() {
+                let j = self.below(i + 1);
+                items.swap(i, j);
+            }
+        }
+
+        // Independent stream for a sub-task, so results do not depend on evaluation order
+        pub fn fork(&mut self) -> Rng {
+            Rng::new(self.next_u64())
+        }
+    }
+}
@@ -1,8 +1,13 @@
 
 pub mod strategy {
-    use crate::Indicators;
+    use crate::{Indicators, MaType};
     use crate::{Backtest, Prams};
+    use crate::engiene::engiene::backtest::FillModel;
     use crate::DataFeed;
+    use crate::data_reader::bars::bars::BarSeries;
+    use crate::indicators::patterns::{Direction, Pattern, Patterns};
+    use crate::indicators::stats::{ols, rolling_ols, zscore_from};
+    use crate::engiene::portfolio::portfolio::Portfolio;
 
 
     pub struct Strategy {
@@ -15,41 +20,105 @@ pub mod strategy {
                 backtest: Backtest::new(data),
             }
         }
+        // Runs on synthetic bars while filling at the real prices of the source feed
+        pub fn from_bars(series: &BarSeries, source: &DataFeed) -> Result<Self, Box<dyn std::error::Error>> {
+            let mut backtest = Backtest::new(series.bars.get_ohlcv());
+            backtest.set_execution(series.execution_feed(source))?;
+            Ok(Strategy { backtest })
+        }
+
         pub fn set_params(&mut self, prams: Prams)  -> Result<(), Box<dyn std::error::Error>>{
             self.backtest.set_params(prams)
         }
-        pub fn next(&mut self, period1: usize, period2: usize) -> Result<(), Box<dyn std::error::Error>> {
+        pub fn next(&mut self, period1: usize, period2: usize, ma_type: MaType) -> Result<(), Box<dyn std::error::Error>> {
             let mut indicators = Indicators {
                 ohlcv: self.backtest.ohlcv.get_ohlcv(),
             };
 
-            let ssma = indicators.SMA(period1 as i64)?;
-            let lsma = indicators.SMA(period2 as i64)?;
+            let ssma = indicators.MA(ma_type, period1 as i64)?;
+            let lsma = indicators.MA(ma_type, period2 as i64)?;
+            let start = (ma_type.lookback(period1.max(period2)) + 2).max(period2);
 
-            let ohlcv = self.backtest.ohlcv.get_ohlcv();
-            let open = &ohlcv.Open;
-            let date = &ohlcv.Date;
+            let mut signals = vec![0; lsma.len()];
+            for j in (start - 1)..lsma.len() {
+                if ssma[j] > lsma[j] && ssma[j - 1] < lsma[j - 1] {
+                    signals[j] = 1;
+                } else if ssma[j] < lsma[j] && ssma[j - 1] > lsma[j - 1] {
+                    signals[j] = -1;
+                }
+            }
+            self.trade_signals(&signals, start)
+        }
 
-            for i in period2..lsma.len() {
-                let long_signal = ssma[i - 1] > lsma[i - 1] && ssma[i - 2] < lsma[i - 2];
-                let short_signal = ssma[i - 1] < lsma[i - 1] && ssma[i - 2] > lsma[i - 2];
+        // Enters long on a bullish and short on a bearish occurrence of any of `patterns`
+        // completed on the previous bar with at least `min_strength`
+        pub fn next_pattern(&mut self, patterns: &[Pattern], min_strength: f64) -> Result<(), Box<dyn std::error::Error>> {
+            let mut detector = Patterns {
+                ohlcv: self.backtest.ohlcv.get_ohlcv(),
+            };
+            let mut signals = Vec::new();
+            for bar in detector.scan()? {
+                let matches = bar
+                    .iter()
+                    .filter(|s| patterns.contains(&s.pattern) && s.strength >= min_strength);
+                let mut long_signal = false;
+                let mut short_signal = false;
+                for signal in matches {
+                    match signal.direction {
+                        Direction::Bullish => long_signal = true,
+                        Direction::Bearish => short_signal = true,
+                        Direction::Neutral => {}
+                    }
+                }
+                // conflicting patterns on the same bar cancel out
+                signals.push(match (long_signal, short_signal) {
+                    (true, false) => 1,
+                    (false, true) => -1,
+                    _ => 0,
+                });
+            }
+            self.trade_signals(&signals, 1)
+        }
 
-                let dt = &date[i];
-                let price = open[i];
-                let quantity = (self.backtest.sizer * self.backtest.balance / price).max(0.0);
+        // Trades one signal per bar (1 long, -1 short, 0 flat) known at that bar's close:
+        // the signal goes long or short on the fill model's bar for it, and a bar without a
+        // signal closes the open position. A position held on a fill bar is bracketed by the
+        // stop-loss and take-profit, and the bar is closed out once its fills are done.
+        // Signals before bar `start - 1` are warm-up and not traded or marked.
+        pub fn trade_signals(&mut self, signals: &[i32], start: usize) -> Result<(), Box<dyn std::error::Error>> {
+            // a fill at the close comes after the bar's range has traded, any other before it
+            let fills_at_close = self.backtest.fill_model == FillModel::SignalClose;
+            for (signal_bar, &signal) in signals.iter().enumerate().skip(start.max(1) - 1) {
+                self.backtest.begin_bar(signal_bar)?;
+                let (dt, price) = match self.backtest.fill(signal_bar) {
+                    Some(fill) => fill,
+                    None => break,
+                };
+                let bar = self.backtest.fill_bar(signal_bar);
+                if fills_at_close {
+                    self.backtest.apply_bracket(bar)?;
+                }
+                // sized on equity: cash alone goes negative under a leveraged long
+                let equity = self.backtest.equity_value(price);
+                let quantity = (self.backtest.sizer * equity / self.backtest.instrument.notional(price, 1.0)).max(0.0);
 
-                if long_signal {
+                // with no equity left to size on a signal places no order
+                if signal > 0 && quantity > 0.0 {
                     self.backtest.buy(dt.clone(), price, quantity)?;
-                } else if short_signal {
+                } else if signal < 0 && quantity > 0.0 {
                     self.backtest.sell(dt.clone(), price, quantity)?;
-                } else if self.backtest.position {
+                } else if signal == 0 && self.backtest.position {
                     let close_quantity = if self.backtest.is_long {
                         self.backtest.long_quantity
                     } else {
                         self.backtest.short_quantity
                     };
-                    self.backtest.close(&dt.clone(), &price, close_quantity)?;
+                    self.backtest.close(&dt, &price, close_quantity)?;
                 }
+                if !fills_at_close {
+                    self.backtest.apply_bracket(bar)?;
+                }
+                self.backtest.end_bar(bar)?;
             }
 
             Ok(())
@@ -63,4 +132,334 @@ pub mod strategy {
             self.backtest.stats()
         }
     }
-}
\ No newline at end of file
+
+    pub enum HedgeRatio {
+        // OLS over the first n bars; trading starts after them
+        Static(usize),
+        // OLS over the trailing n bars, re-estimated every bar
+        Rolling(usize),
+    }
+
+    // Trades the spread y - beta * x on its z-score. Leg 0 is y, leg 1 is x.
+    pub struct PairsStrategy {
+        pub portfolio: Portfolio,
+    }
+
+    impl PairsStrategy {
+        pub fn new(y: DataFeed, x: DataFeed) -> Result<Self, Box<dyn std::error::Error>> {
+            let mut portfolio = Portfolio::new();
+            portfolio.add_leg("y", y)?;
+            portfolio.add_leg("x", x)?;
+            Ok(PairsStrategy { portfolio })
+        }
+
+        pub fn set_params(&mut self, prams: Prams) -> Result<(), Box<dyn std::error::Error>> {
+            self.portfolio.set_params(prams)
+        }
+
+        // Short the spread when z > entry_z, long it when z < -entry_z, flatten once |z| < exit_z.
+        // Both legs get the same notional (sizer * combined balance), so the book is dollar neutral.
+        pub fn next(&mut self, hedge: HedgeRatio, z_period: usize, entry_z: f64, exit_z: f64) -> Result<(), Box<dyn std::error::Error>> {
+            let y = self.portfolio.legs[0].execution_ohlcv();
+            let x = self.portfolio.legs[1].execution_ohlcv();
+            let len = y.Close.len();
+
+            let (spread, spread_start) = match hedge {
+                HedgeRatio::Static(formation) => {
+                    if formation < 2 || formation > len {
+                        return Err("Formation period exceeds data length".into());
+                    }
+                    let fit = ols(&y.Close[..formation], &x.Close[..formation])?;
+                    let spread: Vec<f64> = (0..len).map(|i| y.Close[i] - fit.alpha - fit.beta * x.Close[i]).collect();
+                    (spread, formation - 1)
+                }
+                HedgeRatio::Rolling(window) => {
+                    let (alpha, beta) = rolling_ols(&y.Close, &x.Close, window)?;
+                    let spread: Vec<f64> = (0..len).map(|i| y.Close[i] - alpha[i] - beta[i] * x.Close[i]).collect();
+                    (spread, window - 1)
+                }
+            };
+            let z = zscore_from(&spread, z_period, spread_start)?;
+            let start = spread_start + z_period;
+
+            // 1 long spread (long y, short x), -1 short spread, 0 flat
+            let mut side = 0;
+            for (signal_bar, &signal) in z.iter().enumerate().skip(start - 1) {
+                for leg in self.portfolio.legs.iter_mut() {
+                    leg.begin_bar(signal_bar)?;
+                }
+                let (dt, y_price) = match self.portfolio.legs[0].fill(signal_bar) {
+                    Some(fill) => fill,
+                    None => break,
+                };
+                let x_price = match self.portfolio.legs[1].fill(signal_bar) {
+                    Some((_, price)) => price,
+                    None => break,
+                };
+
+                if side != 0 && ((side == 1 && signal >= -exit_z) || (side == -1 && signal <= exit_z)) {
+                    for (leg, price) in self.portfolio.legs.iter_mut().zip([y_price, x_price]) {
+                        let quantity = if leg.is_long { leg.long_quantity } else { leg.short_quantity };
+                        if leg.position && quantity > 0.0 {
+                            leg.close(&dt, &price, quantity)?;
+                        }
+                    }
+                    side = 0;
+                } else if side == 0 && signal.abs() > entry_z {
+                    let notional = self.portfolio.legs[0].sizer * self.portfolio.balance();
+                    let y_quantity = (notional / self.portfolio.legs[0].instrument.notional(y_price, 1.0)).max(0.0);
+                    let x_quantity = (notional / self.portfolio.legs[1].instrument.notional(x_price, 1.0)).max(0.0);
+                    if signal < 0.0 {
+                        self.portfolio.legs[0].buy(dt.clone(), y_price, y_quantity)?;
+                        self.portfolio.legs[1].sell(dt.clone(), x_price, x_quantity)?;
+                        side = 1;
+                    } else {
+                        self.portfolio.legs[0].sell(dt.clone(), y_price, y_quantity)?;
+                        self.portfolio.legs[1].buy(dt.clone(), x_price, x_quantity)?;
+                        side = -1;
+                    }
+                }
+                // each leg closes out the bar it filled on
+                for leg in self.portfolio.legs.iter_mut() {
+                    let bar = leg.fill_bar(signal_bar);
+                    leg.end_bar(bar)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+            self.portfolio.run()
+        }
+
+        pub fn stats(&self) -> Result<(), Box<dyn std::error::Error>> {
+            self.portfolio.stats()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::engiene::engiene::backtest::PramVal;
+        use crate::indicators::patterns::Pattern;
+        use crate::indicators::stats::engle_granger;
+        use crate::random::random::Rng;
+
+        fn feed(bars: &[(f64, f64, f64, f64)]) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for (k, &(o, h, l, c)) in bars.iter().enumerate() {
+                ohlcv.Date.push(format!("2024-01-{:02}", k + 1));
+                ohlcv.Open.push(o);
+                ohlcv.High.push(h);
+                ohlcv.Low.push(l);
+                ohlcv.Close.push(c);
+                ohlcv.Adj_Close.push(c);
+                ohlcv.Volume.push(1000);
+            }
+            ohlcv
+        }
+
+        fn strategy(bars: &[(f64, f64, f64, f64)]) -> Strategy {
+            let mut strategy = Strategy::new(feed(bars));
+            strategy.backtest.verbose = false;
+            strategy.backtest.set_comission(0.0);
+            strategy.backtest.set_sizer(0.5);
+            strategy
+        }
+
+        #[test]
+        fn signals_trade_on_the_next_bar_and_flatten_without_one() {
+            let bars: Vec<_> = (0..6).map(|k| {
+                let p = 100.0 + k as f64;
+                (p, p + 1.0, p - 1.0, p + 0.5)
+            }).collect();
+            let mut strategy = strategy(&bars);
+            strategy.trade_signals(&[0, 1, 0, -1, 0, 0], 1).unwrap();
+
+            let bt = &strategy.backtest;
+            assert_eq!(bt.trades, 4);
+            assert_eq!(bt.ledger.len(), 2);
+            assert!(bt.ledger[0].is_long && !bt.ledger[1].is_long);
+            assert_eq!(bt.ledger[0].entry_date, "2024-01-03");
+            assert_eq!(bt.ledger[0].exit_date, "2024-01-04");
+            assert_eq!(bt.ledger[1].entry_date, "2024-01-05");
+            // bars 1..6 are marked once each
+            assert_eq!(bt.equity.len(), 5);
+        }
+
+        #[test]
+        fn warmup_bars_are_not_traded_or_marked() {
+            let bars = vec![(100.0, 101.0, 99.0, 100.0); 6];
+            let mut strategy = strategy(&bars);
+            strategy.trade_signals(&[1, 1, 0, 0, 0, 0], 3).unwrap();
+            assert_eq!(strategy.backtest.trades, 0);
+            assert_eq!(strategy.backtest.equity.len(), 3);
+        }
+
+        #[test]
+        fn bullish_engulfing_goes_long_on_the_next_open() {
+            let mut bars: Vec<_> = (0..10).map(|k| {
+                let o = 120.0 - k as f64;
+                (o, o + 0.2, o - 1.2, o - 1.0)
+            }).collect();
+            bars.push((109.8, 111.6, 109.7, 111.5));
+            bars.push((111.6, 112.0, 111.0, 111.8));
+            let mut strategy = strategy(&bars);
+            strategy.next_pattern(&[Pattern::Engulfing], 0.0).unwrap();
+
+            let bt = &strategy.backtest;
+            assert!(bt.position && bt.is_long);
+            assert_eq!(bt.orders.last().unwrap().date, "2024-01-12");
+        }
+
+        #[test]
+        fn signal_close_fills_before_its_bar_is_closed_out() {
+            let bars = vec![(100.0, 101.0, 99.0, 100.0); 4];
+            let mut strategy = strategy(&bars);
+            let mut prams = Prams { data: std::collections::HashMap::new() };
+            prams.data.insert("fill_model".to_string(), PramVal::Str("signal_close".to_string()));
+            prams.data.insert("borrow_rate".to_string(), PramVal::Double(0.036));
+            strategy.set_params(prams).unwrap();
+            // a weekend between the short's entry on Friday and its exit on Monday
+            strategy.backtest.ohlcv.Date = vec!["2024-01-04", "2024-01-05", "2024-01-08", "2024-01-09"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+            strategy.trade_signals(&[0, -1, 0, 0], 1).unwrap();
+
+            let bt = &strategy.backtest;
+            assert_eq!(bt.ledger[0].entry_date, "2024-01-05");
+            assert_eq!(bt.ledger[0].exit_date, "2024-01-08");
+            // 500 shares at 100 borrowed over three nights
+            assert!((bt.total_borrow - 500.0 * 100.0 * 0.036 * 3.0 / 360.0).abs() < 1e-9);
+            assert_eq!(bt.equity.len(), 4);
+        }
+
+        #[test]
+        fn leveraged_longs_are_sized_on_equity_and_survive_the_entry_bar() {
+            let flat = (100.0, 100.0, 100.0, 100.0);
+            let mut strategy = strategy(&[flat, (100.0, 100.0, 50.0, 100.0), flat, flat]);
+            strategy.backtest.set_fill_model(FillModel::SignalClose);
+            strategy.backtest.set_margin(0.25, 0.2).unwrap();
+            strategy.backtest.set_sizer(4.0);
+            // the second long finds no margin room left and places nothing
+            strategy.trade_signals(&[0, 1, 1, -1], 1).unwrap();
+
+            let bt = &strategy.backtest;
+            assert_eq!(bt.ledger.len(), 1);
+            assert!(!bt.ledger[0].liquidated);
+            assert_eq!(bt.ledger[0].entry_date, "2024-01-02");
+            assert_eq!(bt.ledger[0].exit_date, "2024-01-04");
+            assert!(!bt.position);
+        }
+
+        fn bracketed(bar: (f64, f64, f64, f64)) -> Strategy {
+            let mut strategy = strategy(&[(100.0, 101.0, 99.0, 100.0), (100.0, 101.0, 99.0, 100.0), bar]);
+            strategy.backtest.stop_loss = 0.05;
+            strategy.backtest.take_profit = 0.05;
+            strategy.trade_signals(&[0, 1, 0], 1).unwrap();
+            strategy
+        }
+
+        #[test]
+        fn stop_and_target_on_the_same_bar_follow_the_intrabar_path() {
+            // open nearer the high: the target at 105 trades before the stop at 95
+            let strategy = bracketed((100.0, 105.0, 90.0, 100.0));
+            assert!(!strategy.backtest.position);
+            assert!((strategy.backtest.balance - 102500.0).abs() < 1e-9);
+
+            // open nearer the low: the stop comes first
+            let strategy = bracketed((100.0, 110.0, 95.0, 100.0));
+            assert!(!strategy.backtest.position);
+            assert!((strategy.backtest.balance - 97500.0).abs() < 1e-9);
+
+            // neither level reached
+            let strategy = bracketed((100.0, 104.0, 96.0, 100.0));
+            assert!(strategy.backtest.position);
+        }
+
+        // y = 10 + 1.5 x + AR(1) noise around a random walk x
+        fn cointegrated_pair(bars: usize, seed: u64) -> (DataFeed, DataFeed) {
+            let mut rng = Rng::new(seed);
+            let (mut x, mut noise) = (100.0, 0.0);
+            let mut xs = Vec::new();
+            let mut ys = Vec::new();
+            for _ in 0..bars {
+                x += rng.normal();
+                noise = 0.5 * noise + 0.8 * rng.normal();
+                xs.push(x);
+                ys.push(10.0 + 1.5 * x + noise);
+            }
+            let leg = |closes: &[f64]| {
+                let bars: Vec<_> = closes.iter().map(|&c| (c, c + 0.1, c - 0.1, c)).collect();
+                feed(&bars)
+            };
+            (leg(&ys), leg(&xs))
+        }
+
+        fn pairs(y: DataFeed, x: DataFeed) -> PairsStrategy {
+            let mut strategy = PairsStrategy::new(y, x).unwrap();
+            let mut prams = Prams { data: std::collections::HashMap::new() };
+            prams.data.insert("balance".to_string(), PramVal::Double(100000.0));
+            prams.data.insert("sizer".to_string(), PramVal::Double(0.25));
+            prams.data.insert("commission".to_string(), PramVal::Double(0.0));
+            prams.data.insert("verbose".to_string(), PramVal::Bool(false));
+            strategy.set_params(prams).unwrap();
+            strategy
+        }
+
+        #[test]
+        fn pairs_trade_the_spread_z_score_with_equal_notional() {
+            let (y, x) = cointegrated_pair(400, 7);
+            assert!(engle_granger(&y.Close, &x.Close, 1).unwrap().p_value < 0.01);
+
+            let formation = 100;
+            let fit = ols(&y.Close[..formation], &x.Close[..formation]).unwrap();
+            assert!((fit.beta - 1.5).abs() < 0.1);
+            let spread: Vec<f64> = (0..y.Close.len()).map(|i| y.Close[i] - fit.alpha - fit.beta * x.Close[i]).collect();
+            let z = zscore_from(&spread, 20, formation - 1).unwrap();
+
+            let mut strategy = pairs(y.get_ohlcv(), x.get_ohlcv());
+            strategy.next(HedgeRatio::Static(formation), 20, 2.0, 0.5).unwrap();
+            let (y_leg, x_leg) = (&strategy.portfolio.legs[0], &strategy.portfolio.legs[1]);
+            assert!(!y_leg.ledger.is_empty());
+            assert_eq!(y_leg.ledger.len(), x_leg.ledger.len());
+
+            let bar = |date: &str| y.Date.iter().position(|d| d == date).unwrap();
+            for (yt, xt) in y_leg.ledger.iter().zip(x_leg.ledger.iter()) {
+                assert_eq!(yt.entry_date, xt.entry_date);
+                assert_eq!(yt.exit_date, xt.exit_date);
+                assert_ne!(yt.is_long, xt.is_long);
+                // entered on the previous bar's z beyond the entry band, y long when z < 0
+                let entry = bar(&yt.entry_date);
+                assert!(z[entry - 1].abs() > 2.0);
+                assert_eq!(yt.is_long, z[entry - 1] < 0.0);
+                // left once z came back inside the exit band
+                let exit = bar(&yt.exit_date);
+                if exit + 1 < y.Date.len() {
+                    assert!(if yt.is_long { z[exit - 1] >= -0.5 } else { z[exit - 1] <= 0.5 });
+                }
+            }
+
+            // both legs of an entry carry the same notional at the fill price
+            let entry = bar(&y_leg.orders[0].date);
+            let y_notional = y_leg.orders[0].requested * y.Open[entry];
+            let x_notional = x_leg.orders[0].requested * x.Open[entry];
+            assert!((y_notional - x_notional).abs() < 1e-6 * y_notional);
+
+            strategy.run().unwrap();
+            assert!(strategy.portfolio.final_balance() > strategy.portfolio.init_balance());
+        }
+
+        #[test]
+        fn rolling_hedge_ratio_trades_after_its_window() {
+            let (y, x) = cointegrated_pair(300, 11);
+            let mut strategy = pairs(y.get_ohlcv(), x.get_ohlcv());
+            strategy.next(HedgeRatio::Rolling(60), 20, 2.0, 0.5).unwrap();
+            let first = &strategy.portfolio.legs[0].orders[0].date;
+            assert!(y.Date.iter().position(|d| d == first).unwrap() > 60 + 20);
+            assert!(strategy.next(HedgeRatio::Static(1000), 20, 2.0, 0.5).is_err());
+        }
+    }
+}
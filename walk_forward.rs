@@ -0,0 +1,371 @@
+pub mod walk_forward {
+    #![allow(dead_code)]
+    use crate::{Backtest, DataFeed, PramVal, Prams};
+    use crate::optimizer::optimizer::OptResult;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum WindowMode {
+        // fixed-length in-sample window that slides forward
+        Rolling,
+        // in-sample window always starts at the first bar and grows
+        Anchored,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct WindowResult {
+        pub in_sample: (usize, usize),
+        pub out_sample: (usize, usize),
+        pub params: Prams,
+        pub is_objective: f64,
+        pub is_return: f64,
+        pub oos_return: f64,
+        pub oos_sharpe: f64,
+        pub oos_equity: Vec<f64>,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct ParamStability {
+        pub name: String,
+        // numeric parameters only
+        pub mean: f64,
+        pub std: f64,
+        // share of windows that chose the most common value
+        pub mode_share: f64,
+    }
+
+    // Out-of-sample window whose run failed, with the runner's error
+    #[derive(Debug, Clone)]
+    pub struct FailedWindow {
+        pub in_sample: (usize, usize),
+        pub out_sample: (usize, usize),
+        pub error: String,
+    }
+
+    pub struct WalkForwardReport {
+        pub windows: Vec<WindowResult>,
+        pub failed: Vec<FailedWindow>,
+        // out-of-sample equity of every window chained together
+        pub equity: Vec<f64>,
+        // out-of-sample return per bar divided by in-sample return per bar
+        pub efficiency: f64,
+        // one entry per optimised parameter
+        pub stability: Vec<ParamStability>,
+    }
+
+    pub struct WalkForward {
+        pub in_sample: usize,
+        pub out_sample: usize,
+        pub mode: WindowMode,
+        // bars before each out-of-sample window handed to the runner so indicators are ready
+        pub warmup: usize,
+        // parameters the search optimises; only these are reported for stability
+        pub optimised: Vec<String>,
+    }
+
+    impl WalkForward {
+        pub fn new(in_sample: usize, out_sample: usize, mode: WindowMode) -> Self {
+            WalkForward {
+                in_sample,
+                out_sample,
+                mode,
+                warmup: 0,
+                optimised: Vec::new(),
+            }
+        }
+
+        pub fn set_warmup(&mut self, bars: usize) {
+            self.warmup = bars;
+        }
+
+        pub fn set_optimised(&mut self, names: Vec<String>) {
+            self.optimised = names;
+        }
+
+        // (in-sample, out-of-sample) bar ranges, half-open. The last out-of-sample
+        // window may be shorter than `out_sample`.
+        pub fn windows(&self, len: usize) -> Vec<((usize, usize), (usize, usize))> {
+            let mut windows = Vec::new();
+            if self.in_sample == 0 || self.out_sample == 0 {
+                return windows;
+            }
+            let mut k = 0;
+            loop {
+                let is_end = self.in_sample + k * self.out_sample;
+                if is_end >= len {
+                    break;
+                }
+                let is_start = match self.mode {
+                    WindowMode::Rolling => is_end - self.in_sample,
+                    WindowMode::Anchored => 0,
+                };
+                windows.push(((is_start, is_end), (is_end, (is_end + self.out_sample).min(len))));
+                k += 1;
+            }
+            windows
+        }
+
+        // `search` optimises on an in-sample slice and returns results best first;
+        // `make_runner` builds the runner that trades the chosen parameters on the
+        // out-of-sample slice plus `warmup` bars before it. Out-of-sample results are
+        // measured from the bar before the window opens, so a position entered during the
+        // warm-up is carried in at that bar's mark.
+        pub fn run<S, M, R>(&self, data: &DataFeed, search: S, make_runner: M) -> Result<WalkForwardReport, Box<dyn std::error::Error>>
+        where
+            S: Fn(DataFeed) -> Result<Vec<OptResult>, Box<dyn std::error::Error>>,
+            M: Fn(DataFeed) -> R,
+            R: Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>>,
+        {
+            let ranges = self.windows(data.Date.len());
+            if ranges.is_empty() {
+                return Err("Not enough data for a single walk-forward window".into());
+            }
+
+            let mut windows = Vec::new();
+            let mut failed = Vec::new();
+            for ((is_start, is_end), (oos_start, oos_end)) in ranges {
+                let results = search(data.slice(is_start, is_end)?)?;
+                let best = results.first().ok_or("In-sample search returned no results")?;
+                let runner = make_runner(data.slice(oos_start.saturating_sub(self.warmup), oos_end)?);
+                let mut params = best.params.clone();
+                params.data.insert("verbose".to_string(), PramVal::Bool(false));
+                let mut backtest = match runner(&params) {
+                    Ok(backtest) => backtest,
+                    Err(e) => {
+                        failed.push(FailedWindow {
+                            in_sample: (is_start, is_end),
+                            out_sample: (oos_start, oos_end),
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                params.data.remove("verbose");
+                // one mark per traded bar up to the last, so the tail lines up with the window
+                let keep = (oos_end - oos_start + 1).min(backtest.equity.len());
+                backtest.equity = backtest.equity.split_off(backtest.equity.len() - keep);
+                let oos_return = match (backtest.equity.first(), backtest.equity.last()) {
+                    (Some(first), Some(last)) if *first != 0.0 => last / first - 1.0,
+                    _ => 0.0,
+                };
+                windows.push(WindowResult {
+                    in_sample: (is_start, is_end),
+                    out_sample: (oos_start, oos_end),
+                    params,
+                    is_objective: best.objective,
+                    is_return: best.total_return,
+                    oos_return,
+                    oos_sharpe: backtest.sharpe(365.0),
+                    oos_equity: backtest.equity.clone(),
+                });
+            }
+            if windows.is_empty() {
+                return Err("Every out-of-sample window failed".into());
+            }
+
+            let equity = Self::stitch(&windows);
+            let efficiency = Self::efficiency(&windows);
+            let stability = self.stability(&windows);
+            Ok(WalkForwardReport {
+                windows,
+                failed,
+                equity,
+                efficiency,
+                stability,
+            })
+        }
+
+        // Each window's first point is the bar before it opens, which the previous window
+        // already ends on, so after the first window it only sets the scale
+        fn stitch(windows: &[WindowResult]) -> Vec<f64> {
+            let mut equity: Vec<f64> = Vec::new();
+            for w in windows {
+                let first = match w.oos_equity.first() {
+                    Some(v) if *v != 0.0 => *v,
+                    _ => continue,
+                };
+                match equity.last() {
+                    Some(last) => {
+                        let scale = last / first;
+                        equity.extend(w.oos_equity.iter().skip(1).map(|v| v * scale));
+                    }
+                    None => equity.extend(w.oos_equity.iter().cloned()),
+                }
+            }
+            equity
+        }
+
+        fn efficiency(windows: &[WindowResult]) -> f64 {
+            let is_bars: usize = windows.iter().map(|w| w.in_sample.1 - w.in_sample.0).sum();
+            let oos_bars: usize = windows.iter().map(|w| w.out_sample.1 - w.out_sample.0).sum();
+            let is_rate = windows.iter().map(|w| w.is_return).sum::<f64>() / is_bars as f64;
+            let oos_rate = windows.iter().map(|w| w.oos_return).sum::<f64>() / oos_bars as f64;
+            if is_rate == 0.0 {
+                return 0.0;
+            }
+            oos_rate / is_rate
+        }
+
+        fn stability(&self, windows: &[WindowResult]) -> Vec<ParamStability> {
+            let mut stability = Vec::new();
+            for name in self.optimised.iter().cloned() {
+                let values: Vec<&PramVal> = windows.iter().filter_map(|w| w.params.data.get(&name)).collect();
+                let numeric: Vec<f64> = values
+                    .iter()
+                    .filter_map(|v| match v {
+                        PramVal::Int(i) => Some(*i as f64),
+                        PramVal::Double(d) => Some(*d),
+                        _ => None,
+                    })
+                    .collect();
+                let (mean, std) = if numeric.is_empty() {
+                    (0.0, 0.0)
+                } else {
+                    let mean = numeric.iter().sum::<f64>() / numeric.len() as f64;
+                    let var = numeric.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / numeric.len() as f64;
+                    (mean, var.sqrt())
+                };
+                let mode_count = values
+                    .iter()
+                    .map(|v| values.iter().filter(|o| *o == v).count())
+                    .max()
+                    .unwrap_or(0);
+                stability.push(ParamStability {
+                    name,
+                    mean,
+                    std,
+                    mode_share: mode_count as f64 / windows.len() as f64,
+                });
+            }
+            stability
+        }
+    }
+
+    impl WalkForwardReport {
+        pub fn print(&self) {
+            println!("...Walk-Forward...");
+            for w in &self.windows {
+                let mut params: Vec<String> = w.params.data.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                params.sort();
+                println!(
+                    "IS {}..{} OOS {}..{} | IS return {:.4} | OOS return {:.4} | OOS sharpe {:.4} | {}",
+                    w.in_sample.0, w.in_sample.1, w.out_sample.0, w.out_sample.1,
+                    w.is_return, w.oos_return, w.oos_sharpe, params.join(" ")
+                );
+            }
+            if let (Some(first), Some(last)) = (self.equity.first(), self.equity.last()) {
+                println!("Stitched OOS Return: {}", (last / first - 1.0) * 100.0);
+            }
+            for w in &self.failed {
+                println!("OOS {}..{} failed: {}", w.out_sample.0, w.out_sample.1, w.error);
+            }
+            println!("Walk-Forward Efficiency: {}", self.efficiency);
+            for s in &self.stability {
+                println!("{}: mean {:.4} std {:.4} mode share {:.2}", s.name, s.mean, s.std, s.mode_share);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::collections::HashMap;
+
+        fn feed(len: usize) -> DataFeed {
+            let mut ohlcv = DataFeed::new();
+            for k in 0..len {
+                let c = 100.0 + k as f64;
+                ohlcv.Date.push(format!("bar{}", k));
+                ohlcv.Open.push(c);
+                ohlcv.High.push(c);
+                ohlcv.Low.push(c);
+                ohlcv.Close.push(c);
+                ohlcv.Adj_Close.push(c);
+                ohlcv.Volume.push(1000);
+            }
+            ohlcv
+        }
+
+        // best parameters carry a fixed key next to the optimised one
+        fn search(slice: DataFeed) -> Result<Vec<OptResult>, Box<dyn std::error::Error>> {
+            let mut data = HashMap::new();
+            data.insert("period".to_string(), PramVal::Int(slice.Date.len() as i32));
+            data.insert("balance".to_string(), PramVal::Double(1000.0));
+            Ok(vec![OptResult {
+                params: Prams { data },
+                total_return: 0.1,
+                sharpe: 1.0,
+                max_drawdown: 0.0,
+                trades: 1,
+                objective: 1.0,
+                equity: Vec::new(),
+            }])
+        }
+
+        // equity tracks the close, failing on the window whose slice opens at `fail_at`
+        fn runner(slice: DataFeed, fail_at: f64) -> impl Fn(&Prams) -> Result<Backtest, Box<dyn std::error::Error>> {
+            move |_| {
+                if slice.Close[0] == fail_at {
+                    return Err("runner broke".into());
+                }
+                let mut backtest = Backtest::new(slice.clone());
+                backtest.equity = slice.Close.clone();
+                Ok(backtest)
+            }
+        }
+
+        #[test]
+        fn stitched_equity_has_one_point_per_bar() {
+            let mut walk_forward = WalkForward::new(10, 10, WindowMode::Rolling);
+            walk_forward.set_warmup(1);
+            walk_forward.set_optimised(vec!["period".to_string()]);
+            let data = feed(40);
+            let report = walk_forward.run(&data, search, |slice| runner(slice, -1.0)).unwrap();
+
+            assert_eq!(report.windows.len(), 3);
+            assert!(report.failed.is_empty());
+            // the bar before the first window, then every out-of-sample bar once
+            assert_eq!(report.equity.len(), 31);
+            for (stitched, close) in report.equity.iter().zip(&data.Close[9..]) {
+                assert!((stitched - close).abs() < 1e-9);
+            }
+        }
+
+        #[test]
+        fn stability_covers_only_optimised_parameters() {
+            let mut walk_forward = WalkForward::new(10, 10, WindowMode::Anchored);
+            walk_forward.set_optimised(vec!["period".to_string()]);
+            let report = walk_forward.run(&feed(40), search, |slice| runner(slice, -1.0)).unwrap();
+
+            assert_eq!(report.stability.len(), 1);
+            let period = &report.stability[0];
+            assert_eq!(period.name, "period");
+            // anchored in-sample windows of 10, 20 and 30 bars
+            assert!((period.mean - 20.0).abs() < 1e-9);
+            assert!((period.mode_share - 1.0 / 3.0).abs() < 1e-9);
+        }
+
+        #[test]
+        fn failed_windows_are_reported() {
+            let mut walk_forward = WalkForward::new(10, 10, WindowMode::Rolling);
+            walk_forward.set_warmup(1);
+            let data = feed(40);
+            // the middle window's slice opens one warm-up bar before bar 20
+            let report = walk_forward.run(&data, search, |slice| runner(slice, data.Close[19])).unwrap();
+
+            assert_eq!(report.windows.len(), 2);
+            assert_eq!(report.failed.len(), 1);
+            assert_eq!(report.failed[0].out_sample, (20, 30));
+            assert_eq!(report.failed[0].error, "runner broke");
+            // the last window is chained on from where the first one ends
+            assert_eq!(report.equity.len(), 21);
+            assert!((report.equity[10] - data.Close[19]).abs() < 1e-9);
+            assert!((report.equity[11] - data.Close[30] * data.Close[19] / data.Close[29]).abs() < 1e-9);
+
+            let broken = walk_forward.run(&data, search, |slice| {
+                let first = slice.Close[0];
+                runner(slice, first)
+            });
+            assert!(broken.is_err());
+        }
+    }
+}